@@ -0,0 +1,135 @@
+//! Throughput of the chunked, multi-node UTXO set scan for large monitored
+//! sets.
+//!
+//! The bitcoin nodes are mocked, answering every `scantxoutset` call with one
+//! UTXO per descriptor after a fixed delay standing in for the node's pass
+//! over its UTXO set. The numbers therefore measure `spox`'s side of the scan
+//! (descriptors, JSON-RPC round trips, chunk dispatch and merging) and how the
+//! chunks spread across nodes, not the speed of a real node.
+//!
+//! Run with `make bench`, optionally setting `SPOX_BENCH_SCAN_DELAY_MS` (the
+//! simulated per-call delay, 20 ms by default).
+
+use std::num::NonZero;
+use std::time::{Duration, Instant};
+
+use bitcoin::hashes::Hash as _;
+use bitcoin::{BlockHash, PubkeyHash, ScriptBuf, Txid};
+use spox::bitcoin::node::BitcoinCoreClient;
+use spox::bitcoin::scanner::UtxoScanner;
+
+const SCRIPT_COUNTS: [u32; 3] = [10_000, 25_000, 50_000];
+const NODE_COUNTS: [usize; 3] = [1, 2, 4];
+const CHUNK_SIZE: usize = 1000;
+const ROUNDS: u32 = 3;
+
+fn mock_node(server: &mut mockito::Server, delay: Duration) -> mockito::Mock {
+    server
+        .mock("POST", "/")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body_from_request(move |request| {
+            std::thread::sleep(delay);
+            let request: serde_json::Value =
+                serde_json::from_slice(request.body().unwrap()).unwrap();
+            let unspents = request["params"][1]
+                .as_array()
+                .unwrap()
+                .iter()
+                .enumerate()
+                .map(|(vout, descriptor)| {
+                    let descriptor = descriptor.as_str().unwrap();
+                    let script = descriptor.trim_start_matches("raw(").trim_end_matches(')');
+                    serde_json::json!({
+                        "txid": Txid::all_zeros(),
+                        "vout": vout,
+                        "scriptPubKey": script,
+                        "desc": descriptor,
+                        "amount": 0.001,
+                        "height": 100,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            serde_json::json!({
+                "result": {
+                    "success": true,
+                    "height": 100,
+                    "bestblock": BlockHash::all_zeros(),
+                    "unspents": unspents,
+                    "total_amount": 0,
+                },
+                "error": null,
+                "id": request["id"],
+            })
+            .to_string()
+            .into_bytes()
+        })
+        .create()
+}
+
+fn scripts(count: u32) -> Vec<ScriptBuf> {
+    (0..count)
+        .map(|i| {
+            let mut hash = [0; 20];
+            hash[..4].copy_from_slice(&i.to_be_bytes());
+            ScriptBuf::new_p2pkh(&PubkeyHash::from_byte_array(hash))
+        })
+        .collect()
+}
+
+fn main() {
+    let delay = std::env::var("SPOX_BENCH_SCAN_DELAY_MS")
+        .ok()
+        .map(|delay| delay.parse().expect("invalid SPOX_BENCH_SCAN_DELAY_MS"))
+        .map(Duration::from_millis)
+        .unwrap_or(Duration::from_millis(20));
+
+    let max_nodes = NODE_COUNTS.iter().copied().max().unwrap();
+    let mut servers = (0..max_nodes)
+        .map(|_| mockito::Server::new())
+        .collect::<Vec<_>>();
+    let _mocks = servers
+        .iter_mut()
+        .map(|server| mock_node(server, delay))
+        .collect::<Vec<_>>();
+
+    println!(
+        "chunk size {CHUNK_SIZE}, simulated scan delay {} ms, best of {ROUNDS} rounds",
+        delay.as_millis()
+    );
+    println!(
+        "{:>8} {:>6} {:>12} {:>14}",
+        "scripts", "nodes", "time (ms)", "scripts/s"
+    );
+
+    for count in SCRIPT_COUNTS {
+        let scripts = scripts(count);
+        for nodes in NODE_COUNTS {
+            let clients = servers[..nodes]
+                .iter()
+                .map(|server| {
+                    BitcoinCoreClient::new(&server.url(), "user".into(), "pass".into()).unwrap()
+                })
+                .collect();
+            let scanner = UtxoScanner::new(clients, NonZero::new(CHUNK_SIZE).unwrap());
+
+            let elapsed = (0..ROUNDS)
+                .map(|_| {
+                    let start = Instant::now();
+                    let utxos = scanner.get_utxos(&scripts).unwrap();
+                    let elapsed = start.elapsed();
+                    assert_eq!(utxos.len(), scripts.len());
+                    elapsed
+                })
+                .min()
+                .unwrap();
+
+            println!(
+                "{count:>8} {nodes:>6} {:>12.1} {:>14.0}",
+                elapsed.as_secs_f64() * 1000.0,
+                f64::from(count) / elapsed.as_secs_f64()
+            );
+        }
+    }
+}
@@ -0,0 +1,543 @@
+//! Local HTTP/JSON API to manage the monitored deposits at runtime
+//!
+//! All the routes require an `Authorization: Bearer <token>` header:
+//!  - `GET /deposits`: list the monitored deposits addresses
+//!  - `GET /deposits/{alias}`: get a deposit address, with the UTXOs found by
+//!    the last scan and their Emily submission status
+//!  - `POST /deposits/{alias}`: start monitoring a deposit, with the same
+//!    fields as a `[deposit.<alias>]` config entry
+//!  - `DELETE /deposits/{alias}`: stop monitoring a deposit
+//!
+//! If a state file is configured, the changes are persisted to it and applied
+//! on top of the config deposits at startup.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::SocketAddr;
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::Arc;
+
+use axum::extract::{Path, Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+
+use crate::config::{AdminApiConfig, MonitoredDepositConfig, Network, Secret};
+use crate::deposit_monitor::{
+    DepositActivity, DepositAddressInfo, DepositRecord, MonitoredDeposit, MonitoredSet,
+};
+use crate::error::Error;
+use crate::stacks::is_mainnet_principal;
+
+/// The admin API server
+#[derive(Clone)]
+pub struct AdminApi {
+    bind: SocketAddr,
+    token: Secret,
+    network: Network,
+    monitored: MonitoredSet,
+    activity: DepositActivity,
+    /// The state file, if any. The lock is also held while changing the
+    /// monitored deposits, so that the changes are persisted in order.
+    state_file: Arc<Mutex<Option<StateFile>>>,
+}
+
+/// The monitored deposits changes made through the API
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct PersistedState {
+    /// The deposits added through the API, in the request format
+    #[serde(default)]
+    added: BTreeMap<String, serde_json::Value>,
+    /// The config deposits removed through the API
+    #[serde(default)]
+    removed: BTreeSet<String>,
+}
+
+struct StateFile {
+    path: PathBuf,
+    persisted: PersistedState,
+}
+
+impl StateFile {
+    /// Load the state file; a missing file is an empty state
+    fn load(path: &FsPath) -> Result<Self, Error> {
+        let persisted = match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|error| Error::AdminApiStateFile(path.to_path_buf(), error.to_string()))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => PersistedState::default(),
+            Err(error) => {
+                return Err(Error::AdminApiStateFile(
+                    path.to_path_buf(),
+                    error.to_string(),
+                ));
+            }
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            persisted,
+        })
+    }
+
+    /// Write `persisted` to the state file, replacing it atomically. The
+    /// blocking writes run off the async runtime.
+    async fn save(&mut self, persisted: PersistedState) -> Result<(), Error> {
+        let to_error = |error: &dyn std::fmt::Display| {
+            Error::AdminApiStateFile(self.path.clone(), error.to_string())
+        };
+
+        let content = serde_json::to_string_pretty(&persisted).map_err(|e| to_error(&e))?;
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut tmp_path = path.clone().into_os_string();
+            tmp_path.push(".tmp");
+            std::fs::write(&tmp_path, content)?;
+            std::fs::rename(&tmp_path, &path)
+        })
+        .await
+        .map_err(|e| to_error(&e))?
+        .map_err(|e| to_error(&e))?;
+
+        self.persisted = persisted;
+        Ok(())
+    }
+}
+
+/// An API error, returned as `{"error": "<message>"}`
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl ToString) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+        }
+    }
+
+    fn not_found(alias: &str) -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            Error::UnknownAlias(alias.to_string()),
+        )
+    }
+
+    fn internal(error: Error) -> Self {
+        tracing::error!(%error, "admin API internal error");
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, error)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = serde_json::json!({ "error": self.message });
+        (self.status, Json(body)).into_response()
+    }
+}
+
+/// A monitored deposit address, with the UTXOs found by the last scan
+#[derive(Debug, Serialize)]
+struct DepositDetails {
+    #[serde(flatten)]
+    info: DepositAddressInfo,
+    utxos: Vec<DepositRecord>,
+}
+
+impl AdminApi {
+    /// Creates a new `AdminApi`, applying the changes from the state file, if
+    /// any, to the `monitored` deposits.
+    pub fn new(
+        config: &AdminApiConfig,
+        network: Network,
+        monitored: MonitoredSet,
+        activity: DepositActivity,
+    ) -> Result<Self, Error> {
+        let state_file = config
+            .state_file
+            .as_deref()
+            .map(StateFile::load)
+            .transpose()?;
+
+        if let Some(state_file) = &state_file {
+            apply_state(&state_file.persisted, &monitored, network);
+        }
+
+        Ok(Self {
+            bind: config.bind,
+            token: config.token.clone(),
+            network,
+            monitored,
+            activity,
+            state_file: Arc::new(Mutex::new(state_file)),
+        })
+    }
+
+    /// Get the API router
+    pub fn router(self) -> Router {
+        Router::new()
+            .route("/deposits", get(list_deposits))
+            .route(
+                "/deposits/{alias}",
+                get(get_deposit).post(add_deposit).delete(remove_deposit),
+            )
+            .layer(middleware::from_fn_with_state(self.clone(), authenticate))
+            .with_state(self)
+    }
+
+    /// Bind the API listening socket
+    pub async fn bind(&self) -> Result<TcpListener, Error> {
+        let listener = TcpListener::bind(self.bind)
+            .await
+            .map_err(Error::AdminApiServer)?;
+        tracing::info!(bind = %self.bind, "admin API listening");
+
+        Ok(listener)
+    }
+
+    /// Serve the API on `listener` until an error occurs
+    pub async fn serve(self, listener: TcpListener) -> Result<(), Error> {
+        axum::serve(listener, self.router())
+            .await
+            .map_err(Error::AdminApiServer)
+    }
+
+    fn address_info(&self, deposit: &MonitoredDeposit) -> Result<DepositAddressInfo, ApiError> {
+        deposit
+            .to_address_info(self.network.to_bitcoin_network())
+            .map_err(ApiError::internal)
+    }
+}
+
+/// Apply the persisted changes to the monitored deposits. Changes that no
+/// longer apply, e.g. because of a conflicting config entry, are skipped.
+fn apply_state(persisted: &PersistedState, monitored: &MonitoredSet, network: Network) {
+    for alias in &persisted.removed {
+        if monitored.remove(alias).is_some() {
+            tracing::info!(%alias, "removed monitored deposit from the admin API state");
+        }
+    }
+
+    for (alias, value) in &persisted.added {
+        let result = parse_deposit(alias, value.clone(), network).and_then(|deposit| {
+            monitored
+                .insert(deposit)
+                .map_err(|error| ApiError::new(StatusCode::CONFLICT, error))
+        });
+
+        match result {
+            Ok(()) => tracing::info!(%alias, "added monitored deposit from the admin API state"),
+            Err(error) => tracing::warn!(
+                %alias,
+                error = %error.message,
+                "cannot add monitored deposit from the admin API state; skipping it"
+            ),
+        }
+    }
+}
+
+fn parse_deposit(
+    alias: &str,
+    value: serde_json::Value,
+    network: Network,
+) -> Result<MonitoredDeposit, ApiError> {
+    let config = serde_json::from_value::<MonitoredDepositConfig>(value).map_err(|error| {
+        ApiError::new(StatusCode::BAD_REQUEST, format!("invalid deposit: {error}"))
+    })?;
+
+    if is_mainnet_principal(&config.recipient) != network.is_mainnet() {
+        return Err(ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("recipient {} is not a {network} address", config.recipient),
+        ));
+    }
+    if !config
+        .reclaim_script
+        .is_valid_for_network(network.to_bitcoin_network())
+    {
+        return Err(ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("reclaim_script address is not a {network} address"),
+        ));
+    }
+
+    MonitoredDeposit::try_from((&alias.to_string(), &config))
+        .map_err(|error| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, error))
+}
+
+async fn authenticate(State(api): State<AdminApi>, request: Request, next: Next) -> Response {
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match token {
+        Some(token) if constant_time_eq(token.as_bytes(), api.token.expose().as_bytes()) => {
+            next.run(request).await
+        }
+        _ => ApiError::new(StatusCode::UNAUTHORIZED, "missing or invalid bearer token")
+            .into_response(),
+    }
+}
+
+/// Compare two byte strings in a time independent of their content
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn list_deposits(
+    State(api): State<AdminApi>,
+) -> Result<Json<Vec<DepositAddressInfo>>, ApiError> {
+    let infos = api
+        .monitored
+        .list()
+        .iter()
+        .map(|deposit| api.address_info(deposit))
+        .collect::<Result<_, _>>()?;
+
+    Ok(Json(infos))
+}
+
+async fn get_deposit(
+    State(api): State<AdminApi>,
+    Path(alias): Path<String>,
+) -> Result<Json<DepositDetails>, ApiError> {
+    let deposit = api
+        .monitored
+        .get_by_alias(&alias)
+        .ok_or_else(|| ApiError::not_found(&alias))?;
+
+    Ok(Json(DepositDetails {
+        info: api.address_info(&deposit)?,
+        utxos: api.activity.for_alias(&alias),
+    }))
+}
+
+async fn add_deposit(
+    State(api): State<AdminApi>,
+    Path(alias): Path<String>,
+    Json(value): Json<serde_json::Value>,
+) -> Result<(StatusCode, Json<DepositAddressInfo>), ApiError> {
+    let deposit = parse_deposit(&alias, value.clone(), api.network)?;
+    let info = api.address_info(&deposit)?;
+
+    let mut state_file = api.state_file.lock().await;
+
+    api.monitored
+        .insert(deposit)
+        .map_err(|error| ApiError::new(StatusCode::CONFLICT, error))?;
+
+    if let Some(state_file) = state_file.as_mut() {
+        // A removed config deposit stays removed, so that this one replaces
+        // it on restart.
+        let mut persisted = state_file.persisted.clone();
+        persisted.added.insert(alias.clone(), value);
+
+        if let Err(error) = state_file.save(persisted).await {
+            api.monitored.remove(&alias);
+            return Err(ApiError::internal(error));
+        }
+    }
+
+    tracing::info!(%alias, address = %info.address, "added monitored deposit");
+    Ok((StatusCode::CREATED, Json(info)))
+}
+
+async fn remove_deposit(
+    State(api): State<AdminApi>,
+    Path(alias): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let mut state_file = api.state_file.lock().await;
+
+    let deposit = api
+        .monitored
+        .remove(&alias)
+        .ok_or_else(|| ApiError::not_found(&alias))?;
+
+    if let Some(state_file) = state_file.as_mut() {
+        let mut persisted = state_file.persisted.clone();
+        if persisted.added.remove(&alias).is_none() {
+            persisted.removed.insert(alias.clone());
+        }
+
+        if let Err(error) = state_file.save(persisted).await {
+            // Cannot conflict: changes are serialized by the state file lock.
+            let _ = api.monitored.insert(deposit);
+            return Err(ApiError::internal(error));
+        }
+    }
+
+    tracing::info!(%alias, "removed monitored deposit");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Method;
+
+    use super::*;
+
+    const TOKEN: &str = "admin-token";
+    const SIGNERS_XONLY: &str = "c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+    const RECLAIM_XONLY: &str = "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9";
+
+    fn deposit_json(recipient: &str, max_fee: u64) -> serde_json::Value {
+        serde_json::json!({
+            "signers_xonly": SIGNERS_XONLY,
+            "recipient": recipient,
+            "max_fee": max_fee,
+            "lock_time": 10,
+            "reclaim_script": { "type": "p2pk", "pubkey": RECLAIM_XONLY },
+        })
+    }
+
+    fn config_deposits() -> MonitoredSet {
+        let alice = parse_deposit(
+            "alice",
+            deposit_json("ST3497E9JFQ7KB9VEHAZRWYKF3296WQZEXBPXG193", 10_000),
+            Network::Regtest,
+        )
+        .unwrap();
+        MonitoredSet::new(vec![alice])
+    }
+
+    fn admin_api(monitored: MonitoredSet, state_file: Option<PathBuf>) -> AdminApi {
+        let config = AdminApiConfig {
+            bind: "127.0.0.1:0".parse().unwrap(),
+            token: Secret::from(TOKEN.to_string()),
+            state_file,
+        };
+        AdminApi::new(
+            &config,
+            Network::Regtest,
+            monitored,
+            DepositActivity::default(),
+        )
+        .unwrap()
+    }
+
+    async fn spawn_server(api: AdminApi) -> String {
+        let listener = api.bind().await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(api.serve(listener));
+        url
+    }
+
+    async fn call(
+        url: &str,
+        method: Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> (StatusCode, serde_json::Value) {
+        let mut request = reqwest::Client::new()
+            .request(method, format!("{url}{path}"))
+            .bearer_auth(TOKEN);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await.unwrap();
+        let status = response.status();
+        let body = response.json().await.unwrap_or(serde_json::Value::Null);
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn requests_require_the_token() {
+        let url = spawn_server(admin_api(config_deposits(), None)).await;
+        let client = reqwest::Client::new();
+
+        let response = client.get(format!("{url}/deposits")).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = client
+            .get(format!("{url}/deposits"))
+            .bearer_auth("wrong")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let (status, _) = call(&url, Method::GET, "/deposits", None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn manage_deposits_and_persist_changes() {
+        let state_dir = tempfile::tempdir().unwrap();
+        let state_path = state_dir.path().join("state.json");
+
+        let monitored = config_deposits();
+        let api = admin_api(monitored.clone(), Some(state_path.clone()));
+        let url = spawn_server(api).await;
+
+        let bob = deposit_json("ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM", 20_000);
+        let (status, info) = call(&url, Method::POST, "/deposits/bob", Some(bob.clone())).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(info["alias"], "bob");
+        assert_eq!(
+            monitored
+                .get_by_alias("bob")
+                .unwrap()
+                .to_address_info(bitcoin::Network::Regtest)
+                .unwrap()
+                .address,
+            info["address"].as_str().unwrap()
+        );
+
+        let (status, _) = call(&url, Method::POST, "/deposits/bob", Some(bob)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let mainnet = deposit_json("SP3FBR2AGK5H9QBDH3EEN6DF8EK8JY7RX8QJ5SVTE", 20_000);
+        let (status, _) = call(&url, Method::POST, "/deposits/carol", Some(mainnet)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, _) = call(
+            &url,
+            Method::POST,
+            "/deposits/carol",
+            Some(serde_json::json!({})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, list) = call(&url, Method::GET, "/deposits", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let aliases = list
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|info| info["alias"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(aliases, ["alice", "bob"]);
+
+        let (status, details) = call(&url, Method::GET, "/deposits/bob", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(details["max_fee"], 20_000);
+        assert_eq!(details["utxos"], serde_json::json!([]));
+
+        let (status, _) = call(&url, Method::DELETE, "/deposits/alice", None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = call(&url, Method::DELETE, "/deposits/alice", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = call(&url, Method::GET, "/deposits/alice", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // On restart, the changes are applied on top of the config deposits
+        let monitored = config_deposits();
+        admin_api(monitored.clone(), Some(state_path));
+
+        let aliases = monitored
+            .list()
+            .into_iter()
+            .map(|deposit| deposit.alias)
+            .collect::<Vec<_>>();
+        assert_eq!(aliases, ["bob"]);
+    }
+}
@@ -0,0 +1,331 @@
+//! JSON-RPC transport to bitcoin-core over HTTPS, for nodes behind a TLS
+//! terminating reverse proxy. The bitcoin-core RPC client only speaks plain
+//! HTTP.
+
+use std::io::{self, Read as _, Write as _};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs as _};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bitcoin::base64::Engine as _;
+use bitcoin::base64::engine::general_purpose::STANDARD as BASE64;
+use bitcoincore_rpc::jsonrpc::{self, Request, Response, Transport};
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, StreamOwned};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use url::{Host, Url};
+
+/// The timeout for connecting to, writing to and reading from the node
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+/// The maximum size of a response, headers included
+const MAX_RESPONSE_SIZE: u64 = 256 * 1024 * 1024;
+
+/// A JSON-RPC transport sending each request over a new TLS connection
+pub struct HttpsTransport {
+    host: Host,
+    port: u16,
+    /// The request target: the URL path and query
+    target: String,
+    authorization: String,
+    tls: Arc<ClientConfig>,
+}
+
+impl HttpsTransport {
+    /// Create a new transport to the `https` URL, authenticating with the
+    /// given credentials
+    pub fn new(url: &Url, username: &str, password: &str, tls: Arc<ClientConfig>) -> Self {
+        Self {
+            host: url
+                .host()
+                .map_or_else(|| Host::Domain(String::new()), |host| host.to_owned()),
+            port: url.port_or_known_default().unwrap_or(443),
+            target: match url.query() {
+                Some(query) => format!("{}?{query}", url.path()),
+                None => url.path().to_string(),
+            },
+            authorization: BASE64.encode(format!("{username}:{password}")),
+            tls,
+        }
+    }
+
+    fn request<R, T>(&self, request: &R) -> Result<T, jsonrpc::Error>
+    where
+        R: Serialize + ?Sized,
+        T: DeserializeOwned,
+    {
+        let body = serde_json::to_vec(request)?;
+        let (status, response) = self.post(&body).map_err(transport_error)?;
+
+        // bitcoin-core answers RPC errors with a non-200 status and a JSON-RPC
+        // body, so the status only matters if the body is not JSON.
+        serde_json::from_slice(&response).or_else(|error| {
+            if status == 200 {
+                Err(error.into())
+            } else {
+                Err(transport_error(io::Error::other(format!(
+                    "HTTP status {status}"
+                ))))
+            }
+        })
+    }
+
+    /// The addresses of the node, in resolution order
+    fn addresses(&self) -> io::Result<Vec<SocketAddr>> {
+        match &self.host {
+            Host::Domain(domain) => Ok((domain.as_str(), self.port).to_socket_addrs()?.collect()),
+            Host::Ipv4(ip) => Ok(vec![SocketAddr::new(IpAddr::V4(*ip), self.port)]),
+            Host::Ipv6(ip) => Ok(vec![SocketAddr::new(IpAddr::V6(*ip), self.port)]),
+        }
+    }
+
+    /// The name the node certificate is checked against
+    fn server_name(&self) -> io::Result<ServerName<'static>> {
+        match &self.host {
+            Host::Domain(domain) => ServerName::try_from(domain.clone()).map_err(io::Error::other),
+            Host::Ipv4(ip) => Ok(ServerName::IpAddress(IpAddr::V4(*ip).into())),
+            Host::Ipv6(ip) => Ok(ServerName::IpAddress(IpAddr::V6(*ip).into())),
+        }
+    }
+
+    /// Connect to the first reachable address of the node
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut last_error = None;
+        for address in self.addresses()? {
+            match TcpStream::connect_timeout(&address, TIMEOUT) {
+                Ok(tcp) => return Ok(tcp),
+                Err(error) => last_error = Some(error),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| io::Error::other(format!("cannot resolve {}", self.host))))
+    }
+
+    fn post(&self, body: &[u8]) -> io::Result<(u16, Vec<u8>)> {
+        let tcp = self.connect()?;
+        tcp.set_read_timeout(Some(TIMEOUT))?;
+        tcp.set_write_timeout(Some(TIMEOUT))?;
+
+        let server_name = self.server_name()?;
+        let connection =
+            ClientConnection::new(self.tls.clone(), server_name).map_err(io::Error::other)?;
+        let mut stream = StreamOwned::new(connection, tcp);
+
+        // HTTP/1.0 so the response is never chunked and ends with the
+        // connection.
+        write!(
+            stream,
+            "POST {} HTTP/1.0\r\n\
+             Host: {}:{}\r\n\
+             Authorization: Basic {}\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: {}\r\n\r\n",
+            self.target,
+            self.host,
+            self.port,
+            self.authorization,
+            body.len()
+        )?;
+        stream.write_all(body)?;
+        stream.flush()?;
+
+        let mut response = Vec::new();
+        match (&mut stream)
+            .take(MAX_RESPONSE_SIZE + 1)
+            .read_to_end(&mut response)
+        {
+            Ok(_) => {}
+            // Some servers close the connection without a TLS close_notify
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof && !response.is_empty() => {}
+            Err(error) => return Err(error),
+        }
+        if response.len() as u64 > MAX_RESPONSE_SIZE {
+            return Err(io::Error::other(format!(
+                "response larger than {MAX_RESPONSE_SIZE} bytes"
+            )));
+        }
+
+        parse_response(&response)
+    }
+}
+
+impl Transport for HttpsTransport {
+    fn send_request(&self, request: Request) -> Result<Response, jsonrpc::Error> {
+        self.request(&request)
+    }
+
+    fn send_batch(&self, requests: &[Request]) -> Result<Vec<Response>, jsonrpc::Error> {
+        self.request(requests)
+    }
+
+    fn fmt_target(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "https://{}:{}{}", self.host, self.port, self.target)
+    }
+}
+
+/// Split an HTTP response into its status code and body
+fn parse_response(response: &[u8]) -> io::Result<(u16, Vec<u8>)> {
+    let header_end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| io::Error::other("malformed HTTP response"))?;
+    let status = std::str::from_utf8(&response[..header_end])
+        .ok()
+        .and_then(|headers| headers.split_whitespace().nth(1))
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| io::Error::other("malformed HTTP status line"))?;
+
+    Ok((status, response[header_end + 4..].to_vec()))
+}
+
+fn transport_error(error: io::Error) -> jsonrpc::Error {
+    jsonrpc::Error::Transport(Box::new(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+
+    use rustls::pki_types::pem::PemObject as _;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+    use rustls::{ServerConfig, ServerConnection};
+    use test_case::test_case;
+
+    use super::*;
+    use crate::bitcoin::node::BitcoinCoreClient;
+    use crate::config::TlsConfig;
+
+    const CA_CERT: &str = include_str!("../testing/tls/ca.pem");
+    const SERVER_CERT: &str = include_str!("../testing/tls/localhost.pem");
+    const SERVER_KEY: &str = include_str!("../testing/tls/localhost.key");
+
+    /// Answer a single JSON-RPC request over TLS with a block hash, returning
+    /// the request headers
+    fn serve_once(listener: TcpListener) -> JoinHandle<String> {
+        let certs = CertificateDer::pem_slice_iter(SERVER_CERT.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let key = PrivateKeyDer::from_pem_slice(SERVER_KEY.as_bytes()).unwrap();
+        let config =
+            ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(certs, key)
+                .unwrap();
+
+        std::thread::spawn(move || {
+            let (tcp, _) = listener.accept().unwrap();
+            let connection = ServerConnection::new(Arc::new(config)).unwrap();
+            let mut stream = StreamOwned::new(connection, tcp);
+
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+            let (headers, body) = loop {
+                let read = stream.read(&mut buffer).unwrap();
+                assert!(read > 0, "connection closed before the end of the request");
+                request.extend_from_slice(&buffer[..read]);
+                let Some(end) = request.windows(4).position(|window| window == b"\r\n\r\n") else {
+                    continue;
+                };
+                let headers = String::from_utf8(request[..end].to_vec()).unwrap();
+                let length = headers
+                    .lines()
+                    .find_map(|line| line.strip_prefix("Content-Length: "))
+                    .unwrap()
+                    .parse::<usize>()
+                    .unwrap();
+                if request.len() >= end + 4 + length {
+                    break (headers, request[end + 4..].to_vec());
+                }
+            };
+
+            let request: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            let response = serde_json::json!({
+                "result": "00".repeat(32),
+                "error": null,
+                "id": request["id"],
+            })
+            .to_string();
+            write!(
+                stream,
+                "HTTP/1.0 200 OK\r\n\
+                 Content-Type: application/json\r\n\
+                 Content-Length: {}\r\n\r\n\
+                 {response}",
+                response.len()
+            )
+            .unwrap();
+            stream.conn.send_close_notify();
+            stream.flush().unwrap();
+
+            headers
+        })
+    }
+
+    #[test_case("localhost"; "domain")]
+    #[test_case("127.0.0.1"; "ip address")]
+    fn client_calls_tls_server(host: &str) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = serve_once(listener);
+
+        let ca_dir = tempfile::tempdir().unwrap();
+        let ca_cert = ca_dir.path().join("ca.pem");
+        std::fs::write(&ca_cert, CA_CERT).unwrap();
+        let tls = TlsConfig {
+            ca_certs: vec![ca_cert],
+            ..Default::default()
+        };
+        let url = Url::parse(&format!("https://user:pass@{host}:{port}/node?tenant=spox")).unwrap();
+
+        let client = BitcoinCoreClient::with_tls(&url, &tls).unwrap();
+        let block_hash = client.get_block_hash(0).unwrap();
+
+        assert_eq!(block_hash.to_string(), "00".repeat(32));
+        let headers = server.join().unwrap();
+        let mut lines = headers.lines();
+        assert_eq!(lines.next(), Some("POST /node?tenant=spox HTTP/1.0"));
+        let headers = lines.collect::<Vec<_>>();
+        assert!(headers.contains(&format!("Host: {host}:{port}").as_str()));
+        assert!(
+            headers
+                .contains(&format!("Authorization: Basic {}", BASE64.encode("user:pass")).as_str())
+        );
+    }
+
+    #[test]
+    fn ipv6_hosts_are_ip_addresses() {
+        let url = Url::parse("https://[::1]:8332/").unwrap();
+        let tls = crate::tls::rustls_config(&TlsConfig::default()).unwrap();
+        let transport = HttpsTransport::new(&url, "user", "pass", tls);
+
+        assert_eq!(
+            transport.addresses().unwrap(),
+            ["[::1]:8332".parse::<SocketAddr>().unwrap()]
+        );
+        assert_eq!(
+            transport.server_name().unwrap(),
+            ServerName::IpAddress(IpAddr::from(std::net::Ipv6Addr::LOCALHOST).into())
+        );
+        // The Host header and the target keep the brackets
+        assert_eq!(transport.host.to_string(), "[::1]");
+    }
+
+    #[test]
+    fn parse_response_splits_status_and_body() {
+        let response = b"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 2\r\n\r\n{}";
+
+        let (status, body) = parse_response(response).unwrap();
+
+        assert_eq!(status, 500);
+        assert_eq!(body, b"{}");
+    }
+
+    #[test]
+    fn parse_response_rejects_truncated_headers() {
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\n").is_err());
+    }
+}
@@ -2,7 +2,9 @@
 
 use bitcoin;
 
+pub mod https;
 pub mod node;
+pub mod scanner;
 
 /// Bitcoin chain tip
 #[derive(Debug, PartialEq, Eq)]
@@ -0,0 +1,237 @@
+//! Contains the UTXO set scanner for the monitored scripts
+
+use std::num::NonZero;
+
+use bitcoin::{BlockHash, ScriptBuf};
+
+use crate::bitcoin::Utxo;
+use crate::bitcoin::node::BitcoinCoreClient;
+use crate::error::Error;
+
+/// Scans the UTXO set for a (possibly large) set of scripts.
+///
+/// The scripts are split in chunks, so that each `scantxoutset` call stays
+/// within the node limits, and the chunks are spread across the available
+/// bitcoin nodes, which are scanned in parallel: a node can only run one
+/// `scantxoutset` at a time.
+///
+/// Every chunk must be answered at the same chain tip, otherwise the merged
+/// UTXOs could mix two views of the chain (nodes at different heights, or a
+/// block found between two chunks) and the scan fails with
+/// [`Error::ScanTipMismatch`]; the caller retries on its next scan.
+#[derive(Clone)]
+pub struct UtxoScanner {
+    clients: Vec<BitcoinCoreClient>,
+    chunk_size: NonZero<usize>,
+}
+
+impl UtxoScanner {
+    /// Creates a new `UtxoScanner`; `clients` must not be empty.
+    pub fn new(clients: Vec<BitcoinCoreClient>, chunk_size: NonZero<usize>) -> Self {
+        assert!(
+            !clients.is_empty(),
+            "at least one bitcoin client is required"
+        );
+        Self { clients, chunk_size }
+    }
+
+    /// Get UTXOs for scripts
+    pub fn get_utxos<'a, I>(&self, scripts: I) -> Result<Vec<Utxo>, Error>
+    where
+        I: IntoIterator<Item = &'a ScriptBuf>,
+    {
+        let scripts = scripts.into_iter().collect::<Vec<_>>();
+        let chunks = scripts.chunks(self.chunk_size.get()).collect::<Vec<_>>();
+        let clients_count = self.clients.len();
+
+        tracing::debug!(
+            scripts = scripts.len(),
+            chunks = chunks.len(),
+            nodes = clients_count,
+            "scanning the utxo set"
+        );
+
+        if clients_count == 1 || chunks.len() <= 1 {
+            return scan_chunks(&self.clients[0], chunks.iter().copied()).map(|(_, utxos)| utxos);
+        }
+
+        // Chunks are assigned round-robin, each node scanning its own ones
+        // sequentially.
+        let results = std::thread::scope(|scope| {
+            let handles = self
+                .clients
+                .iter()
+                .enumerate()
+                .map(|(index, client)| {
+                    let chunks = chunks
+                        .iter()
+                        .copied()
+                        .skip(index)
+                        .step_by(clients_count)
+                        .collect::<Vec<_>>();
+                    scope.spawn(move || scan_chunks(client, chunks))
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("utxo scan thread panicked"))
+                .collect::<Vec<_>>()
+        });
+
+        let mut tip = None;
+        let mut utxos = Vec::new();
+        for result in results {
+            let (chunks_tip, chunks_utxos) = result?;
+            check_tip(&mut tip, chunks_tip)?;
+            utxos.extend(chunks_utxos);
+        }
+
+        Ok(utxos)
+    }
+}
+
+/// Scans the chunks sequentially, returning the tip they were all scanned at
+/// (`None` if there are no chunks).
+fn scan_chunks<'a, I>(
+    client: &BitcoinCoreClient,
+    chunks: I,
+) -> Result<(Option<BlockHash>, Vec<Utxo>), Error>
+where
+    I: IntoIterator<Item = &'a [&'a ScriptBuf]>,
+{
+    let mut tip = None;
+    let mut utxos = Vec::new();
+    for chunk in chunks {
+        let (chunk_tip, chunk_utxos) = client.get_utxos(chunk.iter().copied())?;
+        check_tip(&mut tip, Some(chunk_tip))?;
+        utxos.extend(chunk_utxos);
+    }
+
+    Ok((tip, utxos))
+}
+
+/// Records the first tip seen, failing if a later one differs.
+fn check_tip(tip: &mut Option<BlockHash>, other: Option<BlockHash>) -> Result<(), Error> {
+    match (*tip, other) {
+        (Some(tip), Some(other)) if tip != other => Err(Error::ScanTipMismatch(tip, other)),
+        (None, other) => {
+            *tip = other;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use bitcoin::hashes::Hash as _;
+    use bitcoin::{PubkeyHash, Txid};
+
+    use super::*;
+
+    /// Mock a bitcoin node answering `scantxoutset` calls with one UTXO per
+    /// requested descriptor.
+    fn mock_node(
+        server: &mut mockito::Server,
+        tip: BlockHash,
+        expected_calls: usize,
+    ) -> mockito::Mock {
+        server
+            .mock("POST", "/")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body_from_request(|request| {
+                let request: serde_json::Value =
+                    serde_json::from_slice(request.body().unwrap()).unwrap();
+                let unspents = request["params"][1]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .enumerate()
+                    .map(|(vout, descriptor)| {
+                        let descriptor = descriptor.as_str().unwrap();
+                        let script = descriptor.trim_start_matches("raw(").trim_end_matches(')');
+                        serde_json::json!({
+                            "txid": Txid::all_zeros(),
+                            "vout": vout,
+                            "scriptPubKey": script,
+                            "desc": descriptor,
+                            "amount": 0.001,
+                            "height": 100,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                serde_json::json!({
+                    "result": {
+                        "success": true,
+                        "height": 100,
+                        "bestblock": tip,
+                        "unspents": unspents,
+                        "total_amount": 0,
+                    },
+                    "error": null,
+                    "id": request["id"],
+                })
+                .to_string()
+                .into_bytes()
+            })
+            .expect(expected_calls)
+            .create()
+    }
+
+    fn scanner(servers: &[&mockito::Server]) -> UtxoScanner {
+        let clients = servers
+            .iter()
+            .map(|server| {
+                BitcoinCoreClient::new(&server.url(), "user".into(), "pass".into()).unwrap()
+            })
+            .collect();
+        UtxoScanner::new(clients, NonZero::new(2).unwrap())
+    }
+
+    fn scripts() -> Vec<ScriptBuf> {
+        (0..5u8)
+            .map(|i| ScriptBuf::new_p2pkh(&PubkeyHash::from_byte_array([i; 20])))
+            .collect()
+    }
+
+    #[test]
+    fn get_utxos_spreads_chunks_across_nodes() {
+        let mut server_a = mockito::Server::new();
+        let mut server_b = mockito::Server::new();
+        let mock_a = mock_node(&mut server_a, BlockHash::all_zeros(), 2);
+        let mock_b = mock_node(&mut server_b, BlockHash::all_zeros(), 1);
+
+        let scanner = scanner(&[&server_a, &server_b]);
+        let scripts = scripts();
+
+        let utxos = scanner.get_utxos(&scripts).unwrap();
+
+        let found = utxos
+            .into_iter()
+            .map(|utxo| utxo.script_pub_key)
+            .collect::<HashSet<_>>();
+        assert_eq!(found, scripts.into_iter().collect::<HashSet<_>>());
+        mock_a.assert();
+        mock_b.assert();
+    }
+
+    #[test]
+    fn get_utxos_rejects_mismatched_tips() {
+        let mut server_a = mockito::Server::new();
+        let mut server_b = mockito::Server::new();
+        let _mock_a = mock_node(&mut server_a, BlockHash::all_zeros(), 2);
+        let _mock_b = mock_node(&mut server_b, BlockHash::from_byte_array([1; 32]), 1);
+
+        let scanner = scanner(&[&server_a, &server_b]);
+
+        assert!(matches!(
+            scanner.get_utxos(&scripts()),
+            Err(Error::ScanTipMismatch(_, _))
+        ));
+    }
+}
@@ -0,0 +1,343 @@
+//! Module to check the configuration semantics, beyond what parsing it
+//! already enforces
+
+use std::collections::HashMap;
+use std::str::FromStr as _;
+
+use bitcoin::opcodes::all::{OP_CHECKSIG, OP_CHECKSIGADD, OP_CHECKSIGVERIFY};
+use bitcoin::script::Instruction;
+use bitcoin::{ScriptBuf, XOnlyPublicKey};
+use emily_client::apis::limits_api;
+
+use crate::config::{MonitoredDepositConfig, Network, Settings};
+use crate::context::Context;
+use crate::deposit_monitor::MonitoredDeposit;
+use crate::limits::DepositLimits;
+use crate::stacks::node::StacksClient;
+use crate::stacks::{is_mainnet_address, is_mainnet_principal};
+
+/// The `signers_xonly` placeholder used in the default config
+pub const PLACEHOLDER_SIGNERS_XONLY: &str =
+    "0000000000000000000000000000000000000000000000000000000000000001";
+
+/// The minimum reclaim lock time: sBTC signers ignore deposits that are
+/// within 3 blocks of their unlock height, so shorter lock times would never
+/// be processed.
+pub const MIN_LOCK_TIME: u32 = 4;
+
+/// The maximum reclaim lock time: BIP-68 block based relative lock times are
+/// limited to 16 bits.
+pub const MAX_LOCK_TIME: u32 = u16::MAX as u32;
+
+/// The maximum deposit `max_fee`; higher values are most likely a mistake.
+pub const MAX_SANE_MAX_FEE: u64 = 1_000_000;
+
+/// A problem found in the configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// The monitored deposit alias, if the issue is specific to one
+    pub alias: Option<String>,
+    /// The issue description
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.alias {
+            Some(alias) => write!(f, "deposit.{alias}: {}", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl ConfigIssue {
+    fn global(message: impl Into<String>) -> Self {
+        Self {
+            alias: None,
+            message: message.into(),
+        }
+    }
+
+    fn deposit(alias: &str, message: impl Into<String>) -> Self {
+        Self {
+            alias: Some(alias.to_string()),
+            message: message.into(),
+        }
+    }
+}
+
+/// Check the whole configuration, returning all the problems found.
+pub async fn check_config(config: &Settings) -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+
+    if let Some(stacks) = &config.stacks {
+        if is_mainnet_address(&stacks.deployer) != config.network().is_mainnet() {
+            issues.push(ConfigIssue::global(format!(
+                "stacks.deployer {} is not a {} address",
+                stacks.deployer, config.network
+            )));
+        }
+    }
+
+    let registry_signers_xonly = match &config.stacks {
+        Some(_) => match fetch_signers_xonly(config).await {
+            Ok(signers_xonly) => Some(signers_xonly),
+            Err(issue) => {
+                issues.push(issue);
+                None
+            }
+        },
+        None => None,
+    };
+
+    let deposits = match config.monitored_deposits() {
+        Ok(deposits) => deposits,
+        Err(error) => {
+            issues.push(ConfigIssue::global(error.to_string()));
+            Vec::new()
+        }
+    };
+
+    let mut script_pubkeys = HashMap::new();
+    for (alias, deposit) in &deposits {
+        issues.extend(
+            check_deposit(deposit, config.network(), registry_signers_xonly.as_ref())
+                .into_iter()
+                .map(|message| ConfigIssue::deposit(alias, message)),
+        );
+
+        match MonitoredDeposit::try_from((alias, deposit)) {
+            Ok(monitored) => {
+                if let Some(other) = script_pubkeys.insert(monitored.to_script_pubkey(), alias) {
+                    issues.push(ConfigIssue::deposit(
+                        alias,
+                        format!("same scriptPubKey as deposit.{other}"),
+                    ));
+                }
+            }
+            Err(error) => issues.push(ConfigIssue::deposit(alias, error.to_string())),
+        }
+    }
+
+    let (endpoint_issues, limits) = check_endpoints(config).await;
+    issues.extend(endpoint_issues);
+
+    if let Some(limits) = limits {
+        for (alias, deposit) in &deposits {
+            if let Err(error) = limits.check_max_fee(deposit.max_fee) {
+                issues.push(ConfigIssue::deposit(alias, error.to_string()));
+            }
+        }
+    }
+
+    issues
+}
+
+async fn fetch_signers_xonly(config: &Settings) -> Result<XOnlyPublicKey, ConfigIssue> {
+    let stacks_client = StacksClient::try_from(config)
+        .map_err(|error| ConfigIssue::global(format!("invalid stacks config: {error}")))?;
+
+    match stacks_client.get_current_signers_aggregate_key().await {
+        Ok(Some(signers_xonly)) => Ok(signers_xonly),
+        Ok(None) => Err(ConfigIssue::global(
+            "no signers aggregate key configured in the registry",
+        )),
+        Err(error) => Err(ConfigIssue::global(format!(
+            "cannot fetch the signers aggregate key from the stacks node: {error}"
+        ))),
+    }
+}
+
+/// Check a monitored deposit config, returning the problems found.
+pub fn check_deposit(
+    deposit: &MonitoredDepositConfig,
+    network: Network,
+    registry_signers_xonly: Option<&XOnlyPublicKey>,
+) -> Vec<String> {
+    let mut issues = Vec::new();
+
+    if is_mainnet_principal(&deposit.recipient) != network.is_mainnet() {
+        issues.push(format!(
+            "recipient {} is not a {network} address",
+            deposit.recipient
+        ));
+    }
+
+    let placeholder = XOnlyPublicKey::from_str(PLACEHOLDER_SIGNERS_XONLY)
+        .expect("the placeholder must be a valid key");
+    if deposit.signers_xonly == placeholder {
+        issues.push("signers_xonly is the placeholder value".to_string());
+    } else if let Some(registry) = registry_signers_xonly {
+        if &deposit.signers_xonly != registry {
+            issues.push(format!(
+                "signers_xonly does not match the registry aggregate key {registry}"
+            ));
+        }
+    }
+
+    if let Some(issue) = check_reclaim_script(&deposit.reclaim_script.script) {
+        issues.push(issue);
+    }
+    if !deposit
+        .reclaim_script
+        .is_valid_for_network(network.to_bitcoin_network())
+    {
+        issues.push(format!("reclaim_script address is not a {network} address"));
+    }
+
+    if !(MIN_LOCK_TIME..=MAX_LOCK_TIME).contains(&deposit.lock_time) {
+        issues.push(format!(
+            "lock_time must be between {MIN_LOCK_TIME} and {MAX_LOCK_TIME} blocks"
+        ));
+    }
+
+    if deposit.max_fee == 0 {
+        issues.push("max_fee must be nonzero".to_string());
+    } else if deposit.max_fee > MAX_SANE_MAX_FEE {
+        issues.push(format!(
+            "max_fee {} is higher than {MAX_SANE_MAX_FEE} sats",
+            deposit.max_fee
+        ));
+    }
+
+    issues
+}
+
+/// Check that the reclaim script can only be spent with a signature.
+fn check_reclaim_script(script: &ScriptBuf) -> Option<String> {
+    if script.is_empty() {
+        return Some(
+            "reclaim_script is empty: anyone can spend the deposit after the lock time".to_string(),
+        );
+    }
+
+    let mut has_signature_check = false;
+    for instruction in script.instructions() {
+        match instruction {
+            Ok(Instruction::Op(op)) => {
+                has_signature_check |=
+                    op == OP_CHECKSIG || op == OP_CHECKSIGVERIFY || op == OP_CHECKSIGADD;
+            }
+            Ok(Instruction::PushBytes(_)) => (),
+            Err(error) => return Some(format!("reclaim_script is not a valid script: {error}")),
+        }
+    }
+
+    if !has_signature_check {
+        return Some("reclaim_script does not check any signature".to_string());
+    }
+
+    None
+}
+
+/// Check the endpoints are reachable, returning the deposit limits from the
+/// first Emily endpoint answering
+async fn check_endpoints(config: &Settings) -> (Vec<ConfigIssue>, Option<DepositLimits>) {
+    let context = match Context::try_from(config) {
+        Ok(context) => context,
+        Err(error) => {
+            return (
+                vec![ConfigIssue::global(format!("invalid endpoint: {error}"))],
+                None,
+            );
+        }
+    };
+
+    let mut issues = Vec::new();
+
+    if let Err(error) = context
+        .bitcoin_client()
+        .check_network(config.network().to_bitcoin_network())
+    {
+        issues.push(ConfigIssue::global(format!("bitcoin node: {error}")));
+    }
+
+    let mut limits = None;
+    for emily_config in context.emily().endpoints() {
+        match limits_api::get_limits(emily_config).await {
+            Ok(emily_limits) => {
+                limits.get_or_insert(DepositLimits::from(&emily_limits));
+            }
+            Err(error) => issues.push(ConfigIssue::global(format!(
+                "cannot reach emily at {}: {error}",
+                emily_config.base_path
+            ))),
+        }
+    }
+
+    (issues, limits)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::secp256k1::SECP256K1;
+    use bitcoin::{NetworkKind, PrivateKey, PublicKey};
+    use clarity::vm::types::PrincipalData;
+    use test_case::test_case;
+
+    use super::*;
+
+    const MAINNET_RECIPIENT: &str = "SP3FBR2AGK5H9QBDH3EEN6DF8EK8JY7RX8QJ5SVTE";
+    const TESTNET_RECIPIENT: &str = "ST3497E9JFQ7KB9VEHAZRWYKF3296WQZEXBPXG193";
+
+    fn random_xonly() -> XOnlyPublicKey {
+        PublicKey::from_private_key(SECP256K1, &PrivateKey::generate(NetworkKind::Test)).into()
+    }
+
+    fn deposit_config() -> MonitoredDepositConfig {
+        let reclaim_key = random_xonly();
+        MonitoredDepositConfig {
+            signers_xonly: random_xonly(),
+            recipient: PrincipalData::parse(TESTNET_RECIPIENT).unwrap(),
+            max_fee: 20_000,
+            lock_time: 10,
+            reclaim_script: bitcoin::script::Builder::new()
+                .push_x_only_key(&reclaim_key)
+                .push_opcode(OP_CHECKSIG)
+                .into_script()
+                .into(),
+        }
+    }
+
+    #[test]
+    fn valid_deposit_has_no_issues() {
+        let deposit = deposit_config();
+        let registry = deposit.signers_xonly;
+
+        assert!(check_deposit(&deposit, Network::Regtest, Some(&registry)).is_empty());
+    }
+
+    #[test_case(|d| d.recipient = PrincipalData::parse(MAINNET_RECIPIENT).unwrap(); "wrong network recipient")]
+    #[test_case(|d| d.signers_xonly = XOnlyPublicKey::from_str(PLACEHOLDER_SIGNERS_XONLY).unwrap(); "placeholder signers")]
+    #[test_case(|d| d.reclaim_script = ScriptBuf::new().into(); "empty reclaim script")]
+    #[test_case(|d| d.reclaim_script = ScriptBuf::from_hex("51").unwrap().into(); "reclaim without signature")]
+    #[test_case(|d| d.reclaim_script.address = Some("bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr".parse().unwrap()); "wrong network reclaim address")]
+    #[test_case(|d| d.lock_time = 1; "lock time too short")]
+    #[test_case(|d| d.lock_time = MAX_LOCK_TIME + 1; "lock time too long")]
+    #[test_case(|d| d.max_fee = 0; "zero max fee")]
+    #[test_case(|d| d.max_fee = MAX_SANE_MAX_FEE + 1; "max fee too high")]
+    fn invalid_deposit_has_one_issue(modify: fn(&mut MonitoredDepositConfig)) {
+        let mut deposit = deposit_config();
+        modify(&mut deposit);
+
+        assert_eq!(check_deposit(&deposit, Network::Regtest, None).len(), 1);
+    }
+
+    #[test]
+    fn registry_mismatch_is_reported() {
+        let deposit = deposit_config();
+
+        let issues = check_deposit(&deposit, Network::Regtest, Some(&random_xonly()));
+        assert_eq!(issues.len(), 1);
+    }
+
+    #[test]
+    fn mainnet_requires_mainnet_recipient() {
+        let mut deposit = deposit_config();
+
+        assert_eq!(check_deposit(&deposit, Network::Mainnet, None).len(), 1);
+
+        deposit.recipient = PrincipalData::parse(MAINNET_RECIPIENT).unwrap();
+        assert!(check_deposit(&deposit, Network::Mainnet, None).is_empty());
+    }
+}
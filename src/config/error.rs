@@ -1,6 +1,10 @@
 //! Configuration errors
+use std::path::PathBuf;
+
 use config::ConfigError;
 
+use crate::config::Network;
+
 /// Configuration error variants
 #[derive(Debug, thiserror::Error)]
 pub enum SpoxConfigError {
@@ -8,6 +12,85 @@ pub enum SpoxConfigError {
     #[error("duration for {0} must be nonzero")]
     ZeroDurationForbidden(&'static str),
 
+    /// An error returned for reclaim scripts that cannot be compiled
+    #[error("invalid reclaim script: {0}")]
+    InvalidReclaimScript(String),
+
+    /// An error returned for addresses not belonging to the configured network
+    #[error("{0} is not a {1} address; check the `network` setting, which defaults to mainnet")]
+    NetworkMismatch(String, Network),
+
+    /// An error returned for a recipients file that cannot be read or parsed
+    #[error("invalid recipients file {0}: {1}")]
+    RecipientsFile(PathBuf, String),
+
+    /// An error returned for deposit template memos that cannot be committed
+    /// to in a script
+    #[error("invalid memo {0}: {1}")]
+    InvalidMemo(String, String),
+
+    /// An error returned for memos with a recipient that is not a contract
+    #[error("memos require a contract principal recipient, got {0}")]
+    NotAContractPrincipal(String),
+
+    /// An error returned when two monitored deposits share the same alias
+    #[error("duplicate monitored deposit alias: {0}")]
+    DuplicateAlias(String),
+
+    /// An error returned when the Emily API key is set both inline and from
+    /// a file
+    #[error("only one of emily.api_key and emily.api_key_file can be set")]
+    ConflictingEmilyApiKey,
+
+    /// An error returned for an Emily API key file that cannot be read
+    #[error("cannot read Emily API key file {0}: {1}")]
+    EmilyApiKeyFile(PathBuf, String),
+
+    /// An error returned when the Emily API key is set but empty
+    #[error("the Emily API key must not be empty")]
+    EmptyEmilyApiKey,
+
+    /// An error returned when no Emily endpoint is configured
+    #[error("at least one Emily endpoint is required")]
+    NoEmilyEndpoint,
+
+    /// An error returned for Emily headers with an invalid name or value
+    #[error("invalid Emily header: {0}")]
+    InvalidEmilyHeader(String),
+
+    /// An error returned when only one of the TLS client certificate and key
+    /// is set
+    #[error("tls.client_cert and tls.client_key must be set together")]
+    IncompleteTlsClientIdentity,
+
+    /// An error returned for TLS files that do not exist
+    #[error("TLS file {0} not found")]
+    MissingTlsFile(PathBuf),
+
+    /// An error returned when the Stacks config has no endpoint
+    #[error("at least one Stacks endpoint is required")]
+    NoStacksEndpoint,
+
+    /// An error returned when the Stacks quorum exceeds the endpoints
+    #[error("the Stacks quorum {0} exceeds the {1} configured endpoints")]
+    StacksQuorumTooLarge(usize, usize),
+
+    /// An error returned when comparing proofs without endpoints to compare
+    #[error("stacks.compare_proofs requires a stacks.quorum of at least 2")]
+    StacksProofsWithoutQuorum,
+
+    /// An error returned for proxy URLs with an unsupported scheme
+    #[error("unsupported proxy scheme {0}, expected http, https, socks5 or socks5h")]
+    UnsupportedProxyScheme(String),
+
+    /// An error returned when the admin API is enabled without a token
+    #[error("the admin API token must not be empty")]
+    EmptyAdminApiToken,
+
+    /// An error returned for webhooks subscribing to unknown events
+    #[error("webhook {0} subscribes to the unknown event {1}")]
+    UnknownWebhookEvent(String, String),
+
     /// An error returned during parsing and building the configuration object
     #[error("cannot parse and build configuration: {0}")]
     ConfigError(#[from] ConfigError),
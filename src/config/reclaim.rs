@@ -0,0 +1,251 @@
+//! Structured reclaim script configuration
+//!
+//! The reclaim script of a deposit can be configured either as a raw hex
+//! script or as one of the templates below. In both cases, the script is the
+//! part of the reclaim script following the `<lock_time> OP_CHECKSEQUENCEVERIFY`
+//! prefix added by sBTC.
+
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::opcodes::all::{OP_CHECKSIG, OP_CHECKSIGADD, OP_NUMEQUAL};
+use bitcoin::script::Builder;
+use bitcoin::secp256k1::SECP256K1;
+use bitcoin::{Address, ScriptBuf, XOnlyPublicKey};
+use serde::Deserialize;
+
+use crate::config::error::SpoxConfigError;
+use crate::config::serialization::{xonly_deserializer, xonly_list_deserializer};
+
+/// The maximum number of keys in a tapscript multisig, bounded by the stack
+/// size limit.
+const MAX_MULTISIG_KEYS: usize = 999;
+
+/// A compiled reclaim script
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReclaimScript {
+    /// The script following `<lock_time> OP_CHECKSEQUENCEVERIFY`
+    pub script: ScriptBuf,
+    /// The address of a `timelocked-address` template, kept to check its
+    /// network against the configured one
+    pub address: Option<Address<NetworkUnchecked>>,
+}
+
+impl From<ScriptBuf> for ReclaimScript {
+    fn from(script: ScriptBuf) -> Self {
+        Self { script, address: None }
+    }
+}
+
+impl ReclaimScript {
+    /// Check that the template address, if any, belongs to `network`
+    pub fn is_valid_for_network(&self, network: bitcoin::Network) -> bool {
+        self.address
+            .as_ref()
+            .is_none_or(|address| address.clone().require_network(network).is_ok())
+    }
+}
+
+/// A reclaim script config, either raw or structured
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum ReclaimScriptConfig {
+    /// A hex-encoded script
+    Raw(String),
+    /// A structured script
+    Template(ReclaimScriptTemplate),
+}
+
+/// A structured reclaim script
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ReclaimScriptTemplate {
+    /// Spendable with a signature of `pubkey`
+    P2pk {
+        /// The key allowed to reclaim the deposit
+        #[serde(deserialize_with = "xonly_deserializer")]
+        pubkey: XOnlyPublicKey,
+    },
+    /// Spendable with at least `threshold` signatures out of `keys`
+    Multisig {
+        /// The number of signatures required
+        threshold: usize,
+        /// The keys allowed to sign
+        #[serde(deserialize_with = "xonly_list_deserializer")]
+        keys: Vec<XOnlyPublicKey>,
+    },
+    /// Spendable by the owner of a key path only (BIP86) taproot `address`,
+    /// signing with its `internal_key`.
+    ///
+    /// The reclaim is a script path spend, signed with the internal key
+    /// rather than the address output key: the wallet must be able to sign a
+    /// tapscript leaf with it, which key path only wallets may not do.
+    TimelockedAddress {
+        /// The taproot address allowed to reclaim the deposit
+        address: Address<NetworkUnchecked>,
+        /// The internal key of the address
+        #[serde(deserialize_with = "xonly_deserializer")]
+        internal_key: XOnlyPublicKey,
+    },
+}
+
+impl ReclaimScriptConfig {
+    /// Compile the config into the reclaim script
+    pub fn to_script(&self) -> Result<ReclaimScript, SpoxConfigError> {
+        match self {
+            ReclaimScriptConfig::Raw(hex) => ScriptBuf::from_hex(hex)
+                .map(ReclaimScript::from)
+                .map_err(|error| SpoxConfigError::InvalidReclaimScript(error.to_string())),
+            ReclaimScriptConfig::Template(template) => Ok(ReclaimScript {
+                script: template.to_script()?,
+                address: match template {
+                    ReclaimScriptTemplate::TimelockedAddress { address, .. } => {
+                        Some(address.clone())
+                    }
+                    _ => None,
+                },
+            }),
+        }
+    }
+}
+
+impl ReclaimScriptTemplate {
+    /// Compile the template into the reclaim script
+    pub fn to_script(&self) -> Result<ScriptBuf, SpoxConfigError> {
+        match self {
+            ReclaimScriptTemplate::P2pk { pubkey } => Ok(p2pk_script(pubkey)),
+            ReclaimScriptTemplate::Multisig { threshold, keys } => {
+                if keys.is_empty() || keys.len() > MAX_MULTISIG_KEYS {
+                    return Err(SpoxConfigError::InvalidReclaimScript(format!(
+                        "multisig must have between 1 and {MAX_MULTISIG_KEYS} keys"
+                    )));
+                }
+                if *threshold == 0 || *threshold > keys.len() {
+                    return Err(SpoxConfigError::InvalidReclaimScript(format!(
+                        "multisig threshold must be between 1 and {}",
+                        keys.len()
+                    )));
+                }
+
+                let mut builder = Builder::new();
+                for (index, key) in keys.iter().enumerate() {
+                    builder = builder.push_x_only_key(key).push_opcode(if index == 0 {
+                        OP_CHECKSIG
+                    } else {
+                        OP_CHECKSIGADD
+                    });
+                }
+
+                Ok(builder
+                    .push_int(*threshold as i64)
+                    .push_opcode(OP_NUMEQUAL)
+                    .into_script())
+            }
+            ReclaimScriptTemplate::TimelockedAddress { address, internal_key } => {
+                // The network is checked against the configured one once the
+                // whole config is loaded.
+                let script_pubkey = address.assume_checked_ref().script_pubkey();
+                if script_pubkey != ScriptBuf::new_p2tr(SECP256K1, *internal_key, None) {
+                    return Err(SpoxConfigError::InvalidReclaimScript(
+                        "timelocked-address must be the key path only taproot address of internal_key"
+                            .to_string(),
+                    ));
+                }
+
+                Ok(p2pk_script(internal_key))
+            }
+        }
+    }
+}
+
+fn p2pk_script(pubkey: &XOnlyPublicKey) -> ScriptBuf {
+    Builder::new()
+        .push_x_only_key(pubkey)
+        .push_opcode(OP_CHECKSIG)
+        .into_script()
+}
+
+#[cfg(test)]
+mod tests {
+    use config::{Config, File, FileFormat};
+    use test_case::test_case;
+
+    use super::*;
+    use crate::config::serialization::reclaim_script_deserializer;
+
+    const KEY1: &str = "c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+    const KEY2: &str = "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9";
+
+    /// The internal key and address of the first BIP86 test vector
+    const BIP86_KEY: &str = "cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115";
+    const BIP86_ADDRESS: &str = "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr";
+
+    #[derive(Deserialize)]
+    struct Wrapper {
+        #[serde(deserialize_with = "reclaim_script_deserializer")]
+        reclaim_script: ReclaimScript,
+    }
+
+    fn parse(toml: &str) -> Result<ReclaimScript, config::ConfigError> {
+        Config::builder()
+            .add_source(File::from_str(toml, FileFormat::Toml))
+            .build()?
+            .try_deserialize::<Wrapper>()
+            .map(|wrapper| wrapper.reclaim_script)
+    }
+
+    #[test_case(r#"reclaim_script = """#, ""; "empty raw")]
+    #[test_case(r#"reclaim_script = "51""#, "51"; "raw")]
+    #[test_case(
+        &format!(r#"reclaim_script = {{ type = "p2pk", pubkey = "{KEY1}" }}"#),
+        &format!("20{KEY1}ac");
+        "p2pk"
+    )]
+    #[test_case(
+        &format!(r#"reclaim_script = {{ type = "multisig", threshold = 2, keys = ["{KEY1}", "{KEY2}"] }}"#),
+        &format!("20{KEY1}ac20{KEY2}ba529c");
+        "multisig"
+    )]
+    #[test_case(
+        &format!(r#"reclaim_script = {{ type = "timelocked-address", address = "{BIP86_ADDRESS}", internal_key = "{BIP86_KEY}" }}"#),
+        &format!("20{BIP86_KEY}ac");
+        "timelocked address"
+    )]
+    fn reclaim_script_config_works(toml: &str, expected_hex: &str) {
+        let reclaim_script = parse(toml).unwrap();
+        assert_eq!(reclaim_script.script.to_hex_string(), expected_hex);
+    }
+
+    #[test_case(bitcoin::Network::Bitcoin, true; "same network")]
+    #[test_case(bitcoin::Network::Testnet, false; "other network")]
+    fn timelocked_address_network_is_checked(network: bitcoin::Network, is_valid: bool) {
+        let reclaim_script = parse(&format!(
+            r#"reclaim_script = {{ type = "timelocked-address", address = "{BIP86_ADDRESS}", internal_key = "{BIP86_KEY}" }}"#
+        ))
+        .unwrap();
+
+        assert_eq!(reclaim_script.is_valid_for_network(network), is_valid);
+        assert!(ReclaimScript::default().is_valid_for_network(network));
+    }
+
+    #[test_case(r#"reclaim_script = "not hex""#; "invalid hex")]
+    #[test_case(
+        &format!(r#"reclaim_script = {{ type = "multisig", threshold = 3, keys = ["{KEY1}", "{KEY2}"] }}"#);
+        "threshold too high"
+    )]
+    #[test_case(r#"reclaim_script = { type = "multisig", threshold = 1, keys = [] }"#; "no keys")]
+    #[test_case(
+        &format!(r#"reclaim_script = {{ type = "timelocked-address", address = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", internal_key = "{BIP86_KEY}" }}"#);
+        "non taproot address"
+    )]
+    #[test_case(
+        &format!(r#"reclaim_script = {{ type = "timelocked-address", address = "{BIP86_ADDRESS}", internal_key = "{KEY1}" }}"#);
+        "wrong internal key"
+    )]
+    #[test_case(
+        &format!(r#"reclaim_script = {{ type = "timelocked-address", address = "{BIP86_ADDRESS}" }}"#);
+        "missing internal key"
+    )]
+    #[test_case(r#"reclaim_script = { type = "unknown" }"#; "unknown type")]
+    fn reclaim_script_config_errors(toml: &str) {
+        assert!(parse(toml).is_err());
+    }
+}
@@ -1,10 +1,12 @@
 use std::str::FromStr as _;
 
-use bitcoin::{ScriptBuf, XOnlyPublicKey, secp256k1};
+use bitcoin::{XOnlyPublicKey, secp256k1};
 use clarity::types::chainstate::StacksAddress;
 use clarity::vm::types::PrincipalData;
 use serde::{Deserialize, Deserializer};
 
+use crate::config::reclaim::{ReclaimScript, ReclaimScriptConfig};
+
 /// A deserializer for the url::Url type. Does not support deserializing a list,
 /// only a single URL.
 pub fn url_deserializer<'de, D>(deserializer: D) -> Result<url::Url, D::Error>
@@ -16,6 +18,41 @@ where
         .map_err(serde::de::Error::custom)
 }
 
+/// A deserializer for a list of url::Url.
+pub fn url_list_deserializer<'de, D>(deserializer: D) -> Result<Vec<url::Url>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    <Vec<String>>::deserialize(deserializer)?
+        .iter()
+        .map(|literal| literal.parse())
+        .collect::<Result<_, _>>()
+        .map_err(serde::de::Error::custom)
+}
+
+/// A deserializer for either a single url::Url or a list of them.
+pub fn url_one_or_many_deserializer<'de, D>(deserializer: D) -> Result<Vec<url::Url>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    let literals = match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(literal) => vec![literal],
+        OneOrMany::Many(literals) => literals,
+    };
+    literals
+        .iter()
+        .map(|literal| literal.parse())
+        .collect::<Result<_, _>>()
+        .map_err(serde::de::Error::custom)
+}
+
 /// A deserializer for the std::time::Duration type.
 /// Serde includes a default deserializer, but it expects a struct.
 pub fn duration_seconds_deserializer<'de, D>(
@@ -55,6 +92,18 @@ where
     PrincipalData::parse(&literal).map_err(serde::de::Error::custom)
 }
 
+/// Parse a list of strings into Stacks PrincipalData
+pub fn principal_list_deserializer<'de, D>(des: D) -> Result<Vec<PrincipalData>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    <Vec<String>>::deserialize(des)?
+        .iter()
+        .map(|literal| PrincipalData::parse(literal))
+        .collect::<Result<_, _>>()
+        .map_err(serde::de::Error::custom)
+}
+
 /// Parse the string into a XOnlyPublicKey
 pub fn xonly_deserializer<'de, D>(des: D) -> Result<XOnlyPublicKey, D::Error>
 where
@@ -64,11 +113,25 @@ where
     secp256k1::XOnlyPublicKey::from_str(&literal).map_err(serde::de::Error::custom)
 }
 
-/// Parse the string into a ScriptBuf
-pub fn script_deserializer<'de, D>(des: D) -> Result<ScriptBuf, D::Error>
+/// Parse a list of strings into XOnlyPublicKeys
+pub fn xonly_list_deserializer<'de, D>(des: D) -> Result<Vec<XOnlyPublicKey>, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
-    let literal = <String>::deserialize(des)?;
-    ScriptBuf::from_hex(&literal).map_err(serde::de::Error::custom)
+    <Vec<String>>::deserialize(des)?
+        .iter()
+        .map(|literal| secp256k1::XOnlyPublicKey::from_str(literal))
+        .collect::<Result<_, _>>()
+        .map_err(serde::de::Error::custom)
+}
+
+/// Parse a raw hex string or a structured reclaim script config into the
+/// reclaim script
+pub fn reclaim_script_deserializer<'de, D>(des: D) -> Result<ReclaimScript, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    ReclaimScriptConfig::deserialize(des)?
+        .to_script()
+        .map_err(serde::de::Error::custom)
 }
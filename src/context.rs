@@ -1,49 +1,235 @@
 //! Application context
 
-use std::sync::Arc;
-
 use emily_client::apis::configuration::Configuration as EmilyConfig;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use url::Url;
 
 use crate::bitcoin::node::BitcoinCoreClient;
-use crate::config::Settings;
+use crate::bitcoin::scanner::UtxoScanner;
+use crate::config::error::SpoxConfigError;
+use crate::config::{EmilyClientConfig, Network, ProxyConfig, Settings, TlsConfig};
+use crate::emily::EmilyClient;
 use crate::error::Error;
+use crate::notifier::Notifier;
+use crate::stacks::node::StacksClient;
+use crate::{proxy, tls};
+
+/// The header carrying the Emily API key
+const EMILY_API_KEY_HEADER: &str = "x-api-key";
 
 /// Application context
 #[derive(Clone)]
 pub struct Context {
+    network: Network,
     bitcoin_client: BitcoinCoreClient,
-    emily_config: Arc<EmilyConfig>,
+    utxo_scanner: UtxoScanner,
+    emily: EmilyClient,
+    stacks_client: Option<StacksClient>,
+    notifier: Notifier,
 }
 
 impl TryFrom<&Settings> for Context {
     type Error = Error;
 
     fn try_from(value: &Settings) -> Result<Self, Self::Error> {
-        let bitcoin_client = BitcoinCoreClient::try_from(&value.bitcoin_rpc_endpoint)?;
-        let emily_config = EmilyConfig {
-            base_path: value
-                .emily_endpoint
-                .to_string()
-                .trim_end_matches('/')
-                .to_string(),
-            ..Default::default()
-        };
+        let bitcoin_client = BitcoinCoreClient::with_tls(&value.bitcoin_rpc_endpoint, &value.tls)?;
+        let scan_clients = std::iter::once(Ok(bitcoin_client.clone()))
+            .chain(
+                value
+                    .bitcoin_scan_endpoints
+                    .iter()
+                    .map(|endpoint| BitcoinCoreClient::with_tls(endpoint, &value.tls)),
+            )
+            .collect::<Result<Vec<_>, _>>()?;
+        let emily_configs = value
+            .emily_endpoint
+            .iter()
+            .map(|endpoint| emily_config(endpoint, &value.emily, &value.tls, value.proxy.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
 
         Ok(Self {
+            network: value.network(),
             bitcoin_client,
-            emily_config: Arc::new(emily_config),
+            utxo_scanner: UtxoScanner::new(scan_clients, value.scan_chunk_size),
+            emily: EmilyClient::new(emily_configs, value.emily.policy),
+            stacks_client: value
+                .stacks
+                .as_ref()
+                .map(|stacks| StacksClient::new(stacks, &value.tls, value.proxy.as_ref()))
+                .transpose()?,
+            notifier: Notifier::new(&value.notifications, &value.tls, value.proxy.as_ref())?,
         })
     }
 }
 
 impl Context {
+    /// Get the network spox runs on
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
     /// Get a reference to the Bitcoin client
     pub fn bitcoin_client(&self) -> &BitcoinCoreClient {
         &self.bitcoin_client
     }
 
-    /// Get a reference to the Emily config
-    pub fn emily_config(&self) -> &EmilyConfig {
-        &self.emily_config
+    /// Get a reference to the UTXO set scanner
+    pub fn utxo_scanner(&self) -> &UtxoScanner {
+        &self.utxo_scanner
+    }
+
+    /// Get a reference to the Emily client
+    pub fn emily(&self) -> &EmilyClient {
+        &self.emily
+    }
+
+    /// Get a reference to the Stacks client, if a Stacks node is configured
+    pub fn stacks_client(&self) -> Option<&StacksClient> {
+        self.stacks_client.as_ref()
+    }
+
+    /// Get a reference to the webhooks notifier
+    pub fn notifier(&self) -> &Notifier {
+        &self.notifier
+    }
+}
+
+/// Build the Emily client config, applying the API key, headers, timeout and
+/// user agent to every request
+fn emily_config(
+    endpoint: &Url,
+    config: &EmilyClientConfig,
+    tls: &TlsConfig,
+    proxy: Option<&ProxyConfig>,
+) -> Result<EmilyConfig, Error> {
+    let user_agent = config
+        .user_agent
+        .clone()
+        .unwrap_or_else(|| format!("spox/{}", env!("CARGO_PKG_VERSION")));
+
+    // The generated client only sends the API key to the endpoints requiring
+    // it, so we send it in the default headers instead, where it is marked as
+    // sensitive: it is not set in the client config, whose `Debug` would
+    // print it.
+    let mut headers = HeaderMap::new();
+    for (name, value) in &config.headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| SpoxConfigError::InvalidEmilyHeader(name.clone()))?;
+        let mut value = HeaderValue::from_str(value.expose())
+            .map_err(|_| SpoxConfigError::InvalidEmilyHeader(name.to_string()))?;
+        value.set_sensitive(true);
+        headers.insert(name, value);
+    }
+    if let Some(api_key) = &config.api_key {
+        let mut value = HeaderValue::from_str(api_key.expose())
+            .map_err(|_| SpoxConfigError::InvalidEmilyHeader(EMILY_API_KEY_HEADER.to_string()))?;
+        value.set_sensitive(true);
+        headers.insert(EMILY_API_KEY_HEADER, value);
+    }
+
+    let builder = reqwest::Client::builder()
+        .timeout(config.timeout)
+        .user_agent(&user_agent)
+        .default_headers(headers);
+    let builder = tls::configure_reqwest(builder, tls)?;
+    let client = proxy::configure_reqwest(builder, proxy)?.build()?;
+
+    Ok(EmilyConfig {
+        base_path: endpoint.to_string().trim_end_matches('/').to_string(),
+        user_agent: Some(user_agent),
+        client,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::config::EmilyPolicy;
+
+    fn emily_client_config() -> EmilyClientConfig {
+        EmilyClientConfig {
+            policy: EmilyPolicy::Failover,
+            api_key: None,
+            api_key_file: None,
+            headers: HashMap::new(),
+            timeout: Duration::from_secs(5),
+            user_agent: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn emily_config_sends_api_key_and_headers() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/limits")
+            .match_header(EMILY_API_KEY_HEADER, "emily-key")
+            .match_header("x-tenant", "spox")
+            .match_header("user-agent", "custom-agent")
+            .with_status(200)
+            .create_async()
+            .await;
+
+        let config = EmilyClientConfig {
+            api_key: Some("emily-key".to_string().into()),
+            headers: HashMap::from([("x-tenant".to_string(), "spox".to_string().into())]),
+            user_agent: Some("custom-agent".to_string()),
+            ..emily_client_config()
+        };
+        let endpoint = format!("{}/", server.url()).parse().unwrap();
+        let emily_config = emily_config(&endpoint, &config, &TlsConfig::default(), None).unwrap();
+
+        assert_eq!(emily_config.base_path, server.url());
+        assert!(!format!("{emily_config:?}").contains("emily-key"));
+        emily_config
+            .client
+            .get(format!("{}/limits", emily_config.base_path))
+            .send()
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+    }
+
+    #[test_case(&[], 1; "proxied")]
+    #[test_case(&["emily.invalid"], 0; "no proxy")]
+    #[tokio::test]
+    async fn emily_requests_go_through_proxy(no_proxy: &[&str], proxied: usize) {
+        // The mock server stands in for the proxy, receiving the requests
+        // for the unresolvable Emily endpoint
+        let mut proxy_server = mockito::Server::new_async().await;
+        let mock = proxy_server
+            .mock("GET", "/limits")
+            .with_status(200)
+            .expect(proxied)
+            .create_async()
+            .await;
+
+        let proxy = ProxyConfig {
+            url: proxy_server.url().parse().unwrap(),
+            no_proxy: no_proxy.iter().map(ToString::to_string).collect(),
+        };
+        let endpoint = "http://emily.invalid:3031".parse().unwrap();
+        let emily_config = emily_config(
+            &endpoint,
+            &emily_client_config(),
+            &TlsConfig::default(),
+            Some(&proxy),
+        )
+        .unwrap();
+
+        let resp = emily_config
+            .client
+            .get(format!("{}/limits", emily_config.base_path))
+            .send()
+            .await;
+
+        assert_eq!(resp.is_ok(), proxied == 1);
+        mock.assert_async().await;
     }
 }
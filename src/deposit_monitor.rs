@@ -1,17 +1,27 @@
 //! Module to monitor for pending deposits
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::num::NonZero;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use bitcoin::{BlockHash, ScriptBuf, Txid};
+use bitcoin::secp256k1::SECP256K1;
+use bitcoin::taproot::{TapTree, TaprootBuilder};
+use bitcoin::{
+    Address, Amount, BlockHash, Denomination, OutPoint, ScriptBuf, Transaction, Txid,
+    XOnlyPublicKey,
+};
 use emily_client::models::CreateDepositRequestBody;
 use lru::LruCache;
-use sbtc::deposits::{DepositScriptInputs, ReclaimScriptInputs};
+use sbtc::deposits::{CreateDepositRequest, DepositScriptInputs, ReclaimScriptInputs};
+use serde::Serialize;
 
 use crate::bitcoin::{BlockRef, Utxo};
 use crate::config::MonitoredDepositConfig;
 use crate::context::Context;
 use crate::error::Error;
+use crate::limits::DepositLimits;
+use crate::notifier::Event;
 
 /// A deposit address to monitor
 #[derive(Debug, Clone)]
@@ -24,7 +34,152 @@ pub struct MonitoredDeposit {
     pub reclaim_script_inputs: ReclaimScriptInputs,
 }
 
+/// The details of a monitored deposit address, as exported by the CLI
+#[derive(Debug, Clone, Serialize)]
+pub struct DepositAddressInfo {
+    /// Monitored deposit alias
+    pub alias: String,
+    /// The deposit address
+    pub address: String,
+    /// The deposit scriptPubKey, hex encoded
+    pub script_pubkey: String,
+    /// The deposit script, hex encoded
+    pub deposit_script: String,
+    /// The reclaim script, hex encoded
+    pub reclaim_script: String,
+    /// The taproot internal key
+    pub taproot_internal_key: String,
+    /// The deposit recipient
+    pub recipient: String,
+    /// The deposit max fee
+    pub max_fee: u64,
+    /// The reclaim lock time
+    pub lock_time: u64,
+}
+
+impl DepositAddressInfo {
+    /// Get the BIP21 URI paying to this address, labelled with the alias
+    pub fn to_bip21_uri(&self, amount: Option<Amount>) -> String {
+        let mut uri = format!("bitcoin:{}?", self.address);
+        if let Some(amount) = amount {
+            uri.push_str(&format!(
+                "amount={}&",
+                amount.to_string_in(Denomination::Bitcoin)
+            ));
+        }
+        uri.push_str("label=");
+        for byte in self.alias.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                    uri.push(byte as char)
+                }
+                _ => uri.push_str(&format!("%{byte:02X}")),
+            }
+        }
+        uri
+    }
+}
+
 impl MonitoredDeposit {
+    /// Get the details of this deposit address on the given network
+    pub fn to_address_info(&self, network: bitcoin::Network) -> Result<DepositAddressInfo, Error> {
+        let script_pubkey = self.to_script_pubkey();
+        let address =
+            Address::from_script(&script_pubkey, network).map_err(Error::InvalidAddress)?;
+
+        Ok(DepositAddressInfo {
+            alias: self.alias.clone(),
+            address: address.to_string(),
+            script_pubkey: script_pubkey.to_hex_string(),
+            deposit_script: self.deposit_script_inputs.deposit_script().to_hex_string(),
+            reclaim_script: self.reclaim_script_inputs.reclaim_script().to_hex_string(),
+            taproot_internal_key: self.taproot_internal_key().to_string(),
+            recipient: self.deposit_script_inputs.recipient.to_string(),
+            max_fee: self.deposit_script_inputs.max_fee,
+            lock_time: self.reclaim_script_inputs.lock_time() as u64,
+        })
+    }
+
+    /// Check a create deposit request built for this deposit, parsing it the
+    /// way sBTC signers do: the transaction output must be a valid deposit,
+    /// worth `amount`, and match this deposit scripts and parameters.
+    pub fn verify_request(
+        &self,
+        request: &CreateDepositRequestBody,
+        amount: Amount,
+        is_mainnet: bool,
+    ) -> Result<(), Error> {
+        let invalid = |reason: String| Error::InvalidDepositRequest(self.alias.clone(), reason);
+
+        let tx: Transaction = bitcoin::consensus::encode::deserialize_hex(&request.transaction_hex)
+            .map_err(|error| invalid(format!("cannot parse the transaction: {error}")))?;
+        let txid = tx.compute_txid();
+        if txid.to_string() != request.bitcoin_txid {
+            return Err(invalid(format!(
+                "the transaction hex is for {txid}, not {}",
+                request.bitcoin_txid
+            )));
+        }
+
+        let script = |hex: &str| {
+            ScriptBuf::from_hex(hex).map_err(|error| invalid(format!("invalid script: {error}")))
+        };
+        let deposit_request = CreateDepositRequest {
+            outpoint: OutPoint::new(txid, request.bitcoin_tx_output_index),
+            deposit_script: script(&request.deposit_script)?,
+            reclaim_script: script(&request.reclaim_script)?,
+        };
+        let info = deposit_request
+            .validate_tx(&tx, is_mainnet)
+            .map_err(|error| invalid(error.to_string()))?;
+
+        let mut mismatches = Vec::new();
+        if info.amount != amount.to_sat() {
+            mismatches.push(format!(
+                "amount {} instead of {}",
+                info.amount,
+                amount.to_sat()
+            ));
+        }
+        let expected = &self.deposit_script_inputs;
+        if info.recipient != expected.recipient {
+            mismatches.push(format!(
+                "recipient {} instead of {}",
+                info.recipient, expected.recipient
+            ));
+        }
+        if info.max_fee != expected.max_fee {
+            mismatches.push(format!(
+                "max fee {} instead of {}",
+                info.max_fee, expected.max_fee
+            ));
+        }
+        if info.signers_public_key != expected.signers_public_key {
+            mismatches.push(format!(
+                "signers key {} instead of {}",
+                info.signers_public_key, expected.signers_public_key
+            ));
+        }
+        if info.deposit_script != self.deposit_script_inputs.deposit_script() {
+            mismatches.push("deposit script".to_string());
+        }
+        if info.reclaim_script != self.reclaim_script_inputs.reclaim_script() {
+            mismatches.push("reclaim script".to_string());
+        }
+
+        if !mismatches.is_empty() {
+            return Err(invalid(format!("mismatching {}", mismatches.join(", "))));
+        }
+
+        Ok(())
+    }
+
+    /// Get the height from which a deposit confirmed at `block_height` can
+    /// be reclaimed
+    pub fn reclaim_unlock_height(&self, block_height: u64) -> u64 {
+        block_height + self.reclaim_script_inputs.lock_time() as u64
+    }
+
     /// Get the scriptPubKey for this deposit address
     pub fn to_script_pubkey(&self) -> ScriptBuf {
         sbtc::deposits::to_script_pubkey(
@@ -32,6 +187,35 @@ impl MonitoredDeposit {
             self.reclaim_script_inputs.reclaim_script(),
         )
     }
+
+    /// Get the taproot internal key for this deposit address. sBTC deposits
+    /// use an unspendable key, so they can only be spent via the script path.
+    pub fn taproot_internal_key(&self) -> XOnlyPublicKey {
+        *sbtc::UNSPENDABLE_TAPROOT_KEY
+    }
+
+    /// Get the taproot tree for this deposit address, made of the deposit
+    /// and the reclaim script leaves.
+    pub fn to_tap_tree(&self) -> Result<TapTree, Error> {
+        let builder = TaprootBuilder::new()
+            .add_leaf(1, self.deposit_script_inputs.deposit_script())
+            .and_then(|builder| builder.add_leaf(1, self.reclaim_script_inputs.reclaim_script()))
+            .map_err(|_| Error::InvalidTaprootTree)?;
+        let tap_tree = TapTree::try_from(builder).map_err(|_| Error::InvalidTaprootTree)?;
+
+        // Sanity check: the tree must commit to the same scriptPubKey sBTC
+        // signers will derive from the deposit and reclaim scripts.
+        let script_pubkey = ScriptBuf::new_p2tr(
+            SECP256K1,
+            self.taproot_internal_key(),
+            Some(tap_tree.root_hash()),
+        );
+        if script_pubkey != self.to_script_pubkey() {
+            return Err(Error::InvalidTaprootTree);
+        }
+
+        Ok(tap_tree)
+    }
 }
 
 impl TryFrom<(&String, &MonitoredDepositConfig)> for MonitoredDeposit {
@@ -48,16 +232,277 @@ impl TryFrom<(&String, &MonitoredDepositConfig)> for MonitoredDeposit {
             },
             reclaim_script_inputs: ReclaimScriptInputs::try_new(
                 deposit.lock_time,
-                deposit.reclaim_script,
+                deposit.reclaim_script.script,
             )?,
         })
     }
 }
 
+/// The monitored deposits, indexed by scriptPubKey and alias. It is a shared
+/// handle, so that they can be changed at runtime while the monitor is
+/// running.
+#[derive(Debug, Clone, Default)]
+pub struct MonitoredSet(Arc<RwLock<MonitoredDeposits>>);
+
+#[derive(Debug, Default)]
+struct MonitoredDeposits {
+    by_script_pubkey: HashMap<ScriptBuf, MonitoredDeposit>,
+    /// The scriptPubKey of each alias
+    aliases: HashMap<String, ScriptBuf>,
+}
+
+impl MonitoredSet {
+    /// Creates a new `MonitoredSet`
+    pub fn new(deposits: Vec<MonitoredDeposit>) -> Self {
+        let aliases = deposits
+            .iter()
+            .map(|m| (m.alias.clone(), m.to_script_pubkey()))
+            .collect();
+        let by_script_pubkey = deposits
+            .into_iter()
+            .map(|m| (m.to_script_pubkey(), m))
+            .collect();
+
+        Self(Arc::new(RwLock::new(MonitoredDeposits {
+            by_script_pubkey,
+            aliases,
+        })))
+    }
+
+    /// Get all the monitored deposits, sorted by alias
+    pub fn list(&self) -> Vec<MonitoredDeposit> {
+        let mut deposits = self
+            .0
+            .read()
+            .expect("monitored set lock poisoned")
+            .by_script_pubkey
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        deposits.sort_by(|a, b| a.alias.cmp(&b.alias));
+        deposits
+    }
+
+    /// Get the scriptPubKeys of all the monitored deposits
+    pub fn script_pubkeys(&self) -> Vec<ScriptBuf> {
+        self.0
+            .read()
+            .expect("monitored set lock poisoned")
+            .by_script_pubkey
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    /// Get the monitored deposit paying to `script_pubkey`
+    pub fn get(&self, script_pubkey: &ScriptBuf) -> Option<MonitoredDeposit> {
+        self.0
+            .read()
+            .expect("monitored set lock poisoned")
+            .by_script_pubkey
+            .get(script_pubkey)
+            .cloned()
+    }
+
+    /// Get the monitored deposit with the given alias
+    pub fn get_by_alias(&self, alias: &str) -> Option<MonitoredDeposit> {
+        let deposits = self.0.read().expect("monitored set lock poisoned");
+        deposits
+            .aliases
+            .get(alias)
+            .and_then(|script_pubkey| deposits.by_script_pubkey.get(script_pubkey))
+            .cloned()
+    }
+
+    /// Start monitoring a deposit; both its alias and scriptPubKey must be
+    /// new.
+    pub fn insert(&self, deposit: MonitoredDeposit) -> Result<(), Error> {
+        let mut deposits = self.0.write().expect("monitored set lock poisoned");
+
+        let script_pubkey = deposit.to_script_pubkey();
+        if let Some(existing) = deposits.by_script_pubkey.get(&script_pubkey) {
+            return Err(Error::DuplicateMonitoredDeposit(existing.alias.clone()));
+        }
+        if deposits.aliases.contains_key(&deposit.alias) {
+            return Err(Error::DuplicateMonitoredDeposit(deposit.alias));
+        }
+
+        deposits
+            .aliases
+            .insert(deposit.alias.clone(), script_pubkey.clone());
+        deposits.by_script_pubkey.insert(script_pubkey, deposit);
+        Ok(())
+    }
+
+    /// Stop monitoring the deposit with the given alias, returning it
+    pub fn remove(&self, alias: &str) -> Option<MonitoredDeposit> {
+        let mut deposits = self.0.write().expect("monitored set lock poisoned");
+
+        let script_pubkey = deposits.aliases.remove(alias)?;
+        deposits.by_script_pubkey.remove(&script_pubkey)
+    }
+}
+
+/// The Emily submission status of a deposit UTXO
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", content = "error", rename_all = "snake_case")]
+pub enum SubmissionStatus {
+    /// Not submitted yet
+    Pending,
+    /// Successfully submitted to Emily
+    Submitted,
+    /// The submission failed
+    Failed(String),
+    /// The deposit is expired, so it is not submitted
+    Expired,
+    /// The signers minted the deposit sBTC
+    Minted,
+}
+
+impl SubmissionStatus {
+    /// Whether the deposit was relayed to Emily and is waiting for the
+    /// signers to mint it
+    pub fn is_awaiting_mint(&self) -> bool {
+        matches!(self, SubmissionStatus::Submitted)
+    }
+}
+
+/// A UTXO confirmed to a monitored deposit address
+#[derive(Debug, Clone, Serialize)]
+pub struct DepositRecord {
+    /// Monitored deposit alias
+    pub alias: String,
+    /// The UTXO transaction id
+    pub txid: Txid,
+    /// The UTXO output index
+    pub vout: u32,
+    /// The UTXO amount, in sats
+    pub amount: u64,
+    /// The height of the block confirming the UTXO
+    pub block_height: u64,
+    /// The Emily submission status
+    #[serde(flatten)]
+    pub status: SubmissionStatus,
+}
+
+/// The UTXOs found by the last scan of the monitored deposits, with their
+/// submission status. It is a shared handle, so that it can be inspected
+/// while the monitor is running.
+#[derive(Debug, Clone, Default)]
+pub struct DepositActivity(Arc<Mutex<HashMap<OutPoint, DepositRecord>>>);
+
+impl DepositActivity {
+    /// Replace the records with the ones from the last scan. Pending records
+    /// keep the status of the same UTXO in the previous scan, if any.
+    ///
+    /// The signers spend the deposit UTXOs when sweeping them, before
+    /// minting, so with `track_mints` the relayed records missing from the
+    /// scan are kept until they are minted or expire. The minted ones are
+    /// dropped once missing from the scan, their mint being already logged.
+    /// Without `track_mints`, e.g. with no Stacks node to check the mints,
+    /// the relayed records are dropped like the others.
+    pub fn update(&self, records: Vec<DepositRecord>, track_mints: bool) {
+        let mut activity = self.0.lock().expect("deposit activity lock poisoned");
+
+        let mut previous = std::mem::take(&mut *activity);
+        for mut record in records {
+            let outpoint = OutPoint::new(record.txid, record.vout);
+            if let Some(previous) = previous.remove(&outpoint) {
+                if record.status == SubmissionStatus::Pending {
+                    record.status = previous.status;
+                }
+            }
+            activity.insert(outpoint, record);
+        }
+
+        if track_mints {
+            activity.extend(
+                previous
+                    .into_iter()
+                    .filter(|(_, record)| record.status == SubmissionStatus::Submitted),
+            );
+        }
+    }
+
+    /// Get the record of a UTXO, if it is known
+    pub fn get(&self, outpoint: &OutPoint) -> Option<DepositRecord> {
+        self.0
+            .lock()
+            .expect("deposit activity lock poisoned")
+            .get(outpoint)
+            .cloned()
+    }
+
+    /// Set the submission status of a UTXO, if it is known
+    pub fn set_status(&self, outpoint: &OutPoint, status: SubmissionStatus) {
+        let mut activity = self.0.lock().expect("deposit activity lock poisoned");
+
+        if let Some(record) = activity.get_mut(outpoint) {
+            record.status = status;
+        }
+    }
+
+    /// Forget the record of a UTXO
+    pub fn remove(&self, outpoint: &OutPoint) -> Option<DepositRecord> {
+        self.0
+            .lock()
+            .expect("deposit activity lock poisoned")
+            .remove(outpoint)
+    }
+
+    /// Get the records of the deposits relayed to Emily and not minted yet
+    pub fn awaiting_mint(&self) -> Vec<DepositRecord> {
+        self.0
+            .lock()
+            .expect("deposit activity lock poisoned")
+            .values()
+            .filter(|record| record.status.is_awaiting_mint())
+            .cloned()
+            .collect()
+    }
+
+    /// Get the records of a monitored deposit, most recent first
+    pub fn for_alias(&self, alias: &str) -> Vec<DepositRecord> {
+        let mut records = self
+            .0
+            .lock()
+            .expect("deposit activity lock poisoned")
+            .values()
+            .filter(|record| record.alias == alias)
+            .cloned()
+            .collect::<Vec<_>>();
+        records.sort_by(|a, b| {
+            b.block_height
+                .cmp(&a.block_height)
+                .then_with(|| a.txid.cmp(&b.txid))
+                .then_with(|| a.vout.cmp(&b.vout))
+        });
+        records
+    }
+}
+
+/// The deposits found by a scan of the monitored deposits
+#[derive(Debug, Default)]
+pub struct PendingDeposits {
+    /// The create deposit requests of the deposits to submit
+    pub requests: Vec<CreateDepositRequestBody>,
+    /// The deposit UTXOs that could not be turned into a request
+    pub failed: usize,
+}
+
 /// Deposit monitor
 pub struct DepositMonitor {
     context: Context,
-    monitored: HashMap<ScriptBuf, MonitoredDeposit>,
+    monitored: MonitoredSet,
+    activity: DepositActivity,
+    /// The pending deposits already reported as nearing expiry
+    expiry_warned: HashSet<OutPoint>,
+    /// Whether a scan completed already. The first one only records the
+    /// UTXOs found, without notifying them, so that restarting does not send
+    /// their events again.
+    seeded: bool,
+    /// The last deposit limits fetched from Emily
+    limits: Option<DepositLimits>,
     tx_hex_cache: LruCache<(Txid, BlockHash), String>,
 }
 
@@ -69,18 +514,59 @@ const TX_HEX_CACHE_SIZE: NonZero<usize> =
 impl DepositMonitor {
     /// Creates a new `DepositMonitor`
     pub fn new(context: Context, monitored: Vec<MonitoredDeposit>) -> Self {
-        let monitored = monitored
-            .into_iter()
-            .map(|m| (m.to_script_pubkey(), m))
-            .collect();
-
         Self {
             context,
-            monitored,
+            monitored: MonitoredSet::new(monitored),
+            activity: DepositActivity::default(),
+            expiry_warned: HashSet::new(),
+            seeded: false,
+            limits: None,
             tx_hex_cache: LruCache::new(TX_HEX_CACHE_SIZE),
         }
     }
 
+    /// Get a handle to the monitored deposits
+    pub fn monitored(&self) -> &MonitoredSet {
+        &self.monitored
+    }
+
+    /// Get a handle to the UTXOs found by the last scan
+    pub fn activity(&self) -> &DepositActivity {
+        &self.activity
+    }
+
+    /// Get the last deposit limits fetched from Emily, if any
+    pub fn limits(&self) -> Option<DepositLimits> {
+        self.limits
+    }
+
+    /// Fetch the deposit limits from Emily, keeping the previous ones if it
+    /// cannot be reached. When they change, the monitored deposits whose max
+    /// fee they make unpayable are reported.
+    pub async fn refresh_limits(&mut self) {
+        let limits = match self.context.emily().get_limits().await {
+            Ok(limits) => DepositLimits::from(&limits),
+            Err(error) => {
+                tracing::warn!(%error, "cannot fetch the deposit limits; keeping the previous ones");
+                return;
+            }
+        };
+
+        if self.limits != Some(limits) {
+            tracing::info!(minimum = ?limits.minimum, cap = ?limits.cap, "deposit limits updated");
+            for deposit in self.monitored.list() {
+                if let Err(error) = limits.check_max_fee(deposit.deposit_script_inputs.max_fee) {
+                    tracing::warn!(
+                        %error,
+                        alias = %deposit.alias,
+                        "the deposits to this address will never be processed"
+                    );
+                }
+            }
+        }
+        self.limits = Some(limits);
+    }
+
     /// Process a `Utxo` to get a create deposit request for Emily
     pub fn get_deposit_from_utxo(
         &mut self,
@@ -92,12 +578,16 @@ impl DepositMonitor {
             .get(&utxo.script_pub_key)
             .ok_or_else(|| Error::MissingMonitoredDeposit(utxo.script_pub_key.clone()))?;
 
-        let unlocking_time =
-            utxo.block_height + (monitored_deposit.reclaim_script_inputs.lock_time() as u64);
+        let unlocking_time = monitored_deposit.reclaim_unlock_height(utxo.block_height);
         if unlocking_time <= chain_tip.block_height {
             return Err(Error::DepositExpired);
         }
 
+        // The signers would never process it
+        if let Some(limits) = &self.limits {
+            limits.check_amount(utxo.amount.to_sat())?;
+        }
+
         let bitcoin_client = self.context.bitcoin_client();
 
         let block_hash = bitcoin_client.get_block_hash(utxo.block_height)?;
@@ -109,7 +599,7 @@ impl DepositMonitor {
             })?
             .clone();
 
-        Ok(CreateDepositRequestBody {
+        let request = CreateDepositRequestBody {
             bitcoin_tx_output_index: utxo.vout,
             bitcoin_txid: utxo.txid.to_string(),
             deposit_script: monitored_deposit
@@ -121,43 +611,530 @@ impl DepositMonitor {
                 .reclaim_script()
                 .to_hex_string(),
             transaction_hex: tx_hex,
-        })
+        };
+
+        // Never send a request the signers would reject
+        monitored_deposit.verify_request(
+            &request,
+            utxo.amount,
+            self.context.network().is_mainnet(),
+        )?;
+
+        Ok(request)
     }
 
-    /// Check pending deposits confirmed to the monitored addresses
-    pub fn get_pending_deposits(
+    /// Get a create deposit request for Emily from a confirmed deposit
+    /// output, spent or not. The output must pay to the `alias` deposit
+    /// address if provided, or to any monitored one otherwise.
+    pub fn get_deposit_from_outpoint(
         &mut self,
+        outpoint: &OutPoint,
+        alias: Option<&str>,
+        block_hash: Option<&BlockHash>,
         chain_tip: &BlockRef,
-    ) -> Result<Vec<CreateDepositRequestBody>, Error> {
-        let utxos = self
+    ) -> Result<CreateDepositRequestBody, Error> {
+        let utxo = self
             .context
             .bitcoin_client()
-            .get_utxos(self.monitored.keys())?;
+            .get_confirmed_output(outpoint, block_hash)?;
+
+        if let Some(alias) = alias {
+            let deposit = self
+                .monitored
+                .get_by_alias(alias)
+                .ok_or_else(|| Error::UnknownAlias(alias.to_string()))?;
+            if deposit.to_script_pubkey() != utxo.script_pub_key {
+                return Err(Error::ScriptPubKeyMismatch(*outpoint, alias.to_string()));
+            }
+        }
 
-        let create_deposits = utxos
+        self.get_deposit_from_utxo(&utxo, chain_tip)
+    }
+
+    /// Notify the lifecycle events of a UTXO found by the current scan,
+    /// compared to the previous one.
+    fn notify_changes(&mut self, record: &DepositRecord, blocks_left: u64) {
+        let notifier = self.context.notifier();
+        let outpoint = OutPoint::new(record.txid, record.vout);
+        let nearing_expiry = record.status != SubmissionStatus::Expired
+            && blocks_left <= notifier.expiry_warning_blocks();
+
+        if !self.seeded {
+            if nearing_expiry {
+                self.expiry_warned.insert(outpoint);
+            }
+            return;
+        }
+
+        let previous = self.activity.get(&outpoint);
+
+        if previous.is_none() {
+            notifier.notify(Event::DepositDetected(record.clone()));
+        }
+
+        let was_expired = previous.is_some_and(|p| p.status == SubmissionStatus::Expired);
+        if record.status == SubmissionStatus::Expired {
+            if !was_expired {
+                notifier.notify(Event::DepositExpired(record.clone()));
+            }
+        } else if nearing_expiry && self.expiry_warned.insert(outpoint) {
+            notifier.notify(Event::DepositNearingExpiry {
+                deposit: record.clone(),
+                blocks_left,
+            });
+        }
+    }
+
+    /// Check pending deposits confirmed to the monitored addresses
+    pub fn get_pending_deposits(&mut self, chain_tip: &BlockRef) -> Result<PendingDeposits, Error> {
+        let utxos = self
+            .context
+            .utxo_scanner()
+            .get_utxos(&self.monitored.script_pubkeys())?;
+
+        let mut records = Vec::with_capacity(utxos.len());
+        let mut pending = PendingDeposits::default();
+
+        for utxo in &utxos {
+            let Some(monitored_deposit) = self.monitored.get(&utxo.script_pub_key) else {
+                tracing::debug!(
+                    txid = %utxo.txid,
+                    vout = %utxo.vout,
+                    "monitored deposit removed during the scan; skipping utxo"
+                );
+                continue;
+            };
+
+            let status = match self.get_deposit_from_utxo(utxo, chain_tip) {
+                Ok(create_deposit) => {
+                    pending.requests.push(create_deposit);
+                    SubmissionStatus::Pending
+                }
+                Err(error @ Error::DepositExpired) => {
+                    tracing::info!(
+                        %error,
+                        txid = %utxo.txid,
+                        vout = %utxo.vout,
+                        block_height = %utxo.block_height,
+                        "deposit is expired; skipping utxo"
+                    );
+                    SubmissionStatus::Expired
+                }
+                Err(error) => {
+                    tracing::warn!(
+                        %error,
+                        txid = %utxo.txid,
+                        vout = %utxo.vout,
+                        block_height = %utxo.block_height,
+                        "failed to get deposit from utxo; skipping utxo"
+                    );
+                    pending.failed += 1;
+                    SubmissionStatus::Failed(error.to_string())
+                }
+            };
+
+            let record = DepositRecord {
+                alias: monitored_deposit.alias.clone(),
+                txid: utxo.txid,
+                vout: utxo.vout,
+                amount: utxo.amount.to_sat(),
+                block_height: utxo.block_height,
+                status,
+            };
+            let blocks_left = monitored_deposit
+                .reclaim_unlock_height(utxo.block_height)
+                .saturating_sub(chain_tip.block_height);
+            self.notify_changes(&record, blocks_left);
+
+            records.push(record);
+        }
+
+        let outpoints = records
             .iter()
-            .flat_map(|utxo| {
-                self.get_deposit_from_utxo(utxo, chain_tip)
-                    .inspect_err(|error| match error {
-                        Error::DepositExpired => tracing::info!(
-                            %error,
-                            txid = %utxo.txid,
-                            vout = %utxo.vout,
-                            block_height = %utxo.block_height,
-                            "deposit is expired; skipping utxo"
-                        ),
-                        _ => tracing::warn!(
-                            %error,
-                            txid = %utxo.txid,
-                            vout = %utxo.vout,
-                            block_height = %utxo.block_height,
-                            "failed to get deposit from utxo; skipping utxo"
-                        ),
-                    })
-                    .ok()
-            })
-            .collect();
+            .map(|record| OutPoint::new(record.txid, record.vout))
+            .collect::<HashSet<_>>();
+        self.expiry_warned
+            .retain(|outpoint| outpoints.contains(outpoint));
+        let track_mints = self.context.stacks_client().is_some();
+        self.activity.update(records, track_mints);
+        self.seeded = true;
+
+        Ok(pending)
+    }
+
+    /// Check the `sbtc-registry` contract for the deposits relayed to Emily
+    /// and mark the completed ones as minted, logging how long after the
+    /// Bitcoin confirmation the mint was detected. Mints are only checked at
+    /// new blocks, so this is an upper bound of the mint latency. Deposits
+    /// not minted by their reclaim unlock height are no longer tracked. Does
+    /// nothing without a Stacks client.
+    pub async fn update_mint_status(&self, chain_tip: &BlockRef) -> Result<(), Error> {
+        let Some(stacks_client) = self.context.stacks_client() else {
+            return Ok(());
+        };
+
+        for record in self.activity.awaiting_mint() {
+            let outpoint = OutPoint::new(record.txid, record.vout);
+
+            match stacks_client.get_deposit_status(&outpoint).await {
+                Ok(Some(true)) => {}
+                Ok(_) => {
+                    let expired =
+                        self.monitored
+                            .get_by_alias(&record.alias)
+                            .is_none_or(|deposit| {
+                                deposit.reclaim_unlock_height(record.block_height)
+                                    <= chain_tip.block_height
+                            });
+                    if expired {
+                        tracing::warn!(
+                            alias = %record.alias,
+                            txid = %record.txid,
+                            vout = %record.vout,
+                            "deposit not minted by its reclaim unlock height; no longer tracked"
+                        );
+                        self.activity.remove(&outpoint);
+                    }
+                    continue;
+                }
+                Err(error) => {
+                    tracing::warn!(
+                        %error,
+                        txid = %record.txid,
+                        vout = %record.vout,
+                        "cannot get the deposit status from the sbtc registry"
+                    );
+                    continue;
+                }
+            }
+
+            self.activity
+                .set_status(&outpoint, SubmissionStatus::Minted);
+
+            let detected_after_blocks = chain_tip.block_height.saturating_sub(record.block_height);
+            match self
+                .context
+                .bitcoin_client()
+                .get_block_time(record.block_height)
+            {
+                Ok(confirmed_at) => {
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs();
+                    tracing::info!(
+                        alias = %record.alias,
+                        txid = %record.txid,
+                        vout = %record.vout,
+                        amount = %record.amount,
+                        detected_after_blocks,
+                        detected_after_secs = now.saturating_sub(confirmed_at),
+                        "deposit minted"
+                    );
+                }
+                Err(error) => tracing::info!(
+                    %error,
+                    alias = %record.alias,
+                    txid = %record.txid,
+                    vout = %record.vout,
+                    amount = %record.amount,
+                    detected_after_blocks,
+                    "deposit minted; cannot get its confirmation time"
+                ),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash as _;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::testing::monitored_deposit;
+
+    fn deposit_record(alias: &str, vout: u32, status: SubmissionStatus) -> DepositRecord {
+        DepositRecord {
+            alias: alias.to_string(),
+            txid: Txid::all_zeros(),
+            vout,
+            amount: 100_000,
+            block_height: 100 + vout as u64,
+            status,
+        }
+    }
+
+    fn address_info(alias: &str) -> DepositAddressInfo {
+        DepositAddressInfo {
+            alias: alias.to_string(),
+            address: "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr".to_string(),
+            script_pubkey: String::new(),
+            deposit_script: String::new(),
+            reclaim_script: String::new(),
+            taproot_internal_key: String::new(),
+            recipient: String::new(),
+            max_fee: 0,
+            lock_time: 0,
+        }
+    }
+
+    #[test_case("alice", None, "label=alice"; "label only")]
+    #[test_case("alice", Some(100_000), "amount=0.001&label=alice"; "with amount")]
+    #[test_case("alice & bob", Some(150_000_000), "amount=1.5&label=alice%20%26%20bob"; "escaped label")]
+    fn bip21_uri_works(alias: &str, sats: Option<u64>, expected_query: &str) {
+        let info = address_info(alias);
+
+        let uri = info.to_bip21_uri(sats.map(Amount::from_sat));
+
+        assert_eq!(uri, format!("bitcoin:{}?{expected_query}", info.address));
+    }
+
+    #[test]
+    fn monitored_set_rejects_duplicates() {
+        let alice = monitored_deposit("alice");
+        let set = MonitoredSet::new(vec![alice.clone()]);
+
+        let mut same_script = alice.clone();
+        same_script.alias = "bob".to_string();
+        assert!(matches!(
+            set.insert(same_script),
+            Err(Error::DuplicateMonitoredDeposit(alias)) if alias == "alice"
+        ));
+        assert!(matches!(
+            set.insert(monitored_deposit("alice")),
+            Err(Error::DuplicateMonitoredDeposit(_))
+        ));
+
+        set.insert(monitored_deposit("bob")).unwrap();
+        let aliases = set.list().into_iter().map(|d| d.alias).collect::<Vec<_>>();
+        assert_eq!(aliases, ["alice", "bob"]);
+        assert_eq!(set.get(&alice.to_script_pubkey()).unwrap().alias, "alice");
+    }
+
+    #[test]
+    fn monitored_set_remove_works() {
+        let set = MonitoredSet::new(vec![monitored_deposit("alice")]);
+        let handle = set.clone();
+
+        assert!(handle.remove("bob").is_none());
+        assert_eq!(handle.remove("alice").unwrap().alias, "alice");
+        assert!(set.get_by_alias("alice").is_none());
+        assert!(set.script_pubkeys().is_empty());
+    }
+
+    #[test]
+    fn deposit_activity_keeps_known_status() {
+        let activity = DepositActivity::default();
+        activity.update(
+            vec![
+                deposit_record("alice", 0, SubmissionStatus::Pending),
+                deposit_record("alice", 1, SubmissionStatus::Pending),
+                deposit_record("bob", 2, SubmissionStatus::Pending),
+            ],
+            true,
+        );
+        activity.set_status(
+            &OutPoint::new(Txid::all_zeros(), 0),
+            SubmissionStatus::Submitted,
+        );
+
+        // The UTXO 1 is spent, and a new expired UTXO is found.
+        activity.update(
+            vec![
+                deposit_record("alice", 0, SubmissionStatus::Pending),
+                deposit_record("alice", 3, SubmissionStatus::Expired),
+            ],
+            true,
+        );
+
+        let records = activity
+            .for_alias("alice")
+            .into_iter()
+            .map(|record| (record.vout, record.status))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            records,
+            [
+                (3, SubmissionStatus::Expired),
+                (0, SubmissionStatus::Submitted)
+            ]
+        );
+        assert!(activity.for_alias("bob").is_empty());
+    }
+
+    #[test]
+    fn deposit_activity_keeps_swept_deposits_until_minted() {
+        let activity = DepositActivity::default();
+        activity.update(
+            vec![
+                deposit_record("alice", 0, SubmissionStatus::Pending),
+                deposit_record("alice", 1, SubmissionStatus::Pending),
+                deposit_record("alice", 2, SubmissionStatus::Pending),
+            ],
+            true,
+        );
+        activity.set_status(
+            &OutPoint::new(Txid::all_zeros(), 0),
+            SubmissionStatus::Submitted,
+        );
+        activity.set_status(
+            &OutPoint::new(Txid::all_zeros(), 1),
+            SubmissionStatus::Minted,
+        );
+
+        // The signers swept all the UTXOs.
+        activity.update(vec![], true);
+
+        let records = activity
+            .for_alias("alice")
+            .into_iter()
+            .map(|record| (record.vout, record.status))
+            .collect::<Vec<_>>();
+        assert_eq!(records, [(0, SubmissionStatus::Submitted)]);
+        let awaiting_mint = activity.awaiting_mint();
+        assert_eq!(awaiting_mint.len(), 1);
+        assert_eq!(awaiting_mint[0].vout, 0);
+    }
+
+    #[test]
+    fn deposit_activity_stays_bounded_over_many_mints() {
+        let activity = DepositActivity::default();
+        for vout in 0..10 {
+            let outpoint = OutPoint::new(Txid::all_zeros(), vout);
+            activity.update(
+                vec![deposit_record("alice", vout, SubmissionStatus::Pending)],
+                true,
+            );
+            activity.set_status(&outpoint, SubmissionStatus::Submitted);
+
+            // The signers swept the UTXO, then minted the deposit.
+            activity.update(vec![], true);
+            activity.set_status(&outpoint, SubmissionStatus::Minted);
+
+            let records = activity
+                .for_alias("alice")
+                .into_iter()
+                .map(|record| (record.vout, record.status))
+                .collect::<Vec<_>>();
+            assert_eq!(records, [(vout, SubmissionStatus::Minted)]);
+        }
+
+        activity.update(vec![], true);
+        assert!(activity.for_alias("alice").is_empty());
+        assert!(activity.awaiting_mint().is_empty());
+    }
+
+    #[test]
+    fn deposit_activity_drops_swept_deposits_without_mint_tracking() {
+        let activity = DepositActivity::default();
+        activity.update(
+            vec![deposit_record("alice", 0, SubmissionStatus::Pending)],
+            false,
+        );
+        activity.set_status(
+            &OutPoint::new(Txid::all_zeros(), 0),
+            SubmissionStatus::Submitted,
+        );
+
+        // The signers swept the UTXO, and nothing checks its mint.
+        activity.update(vec![], false);
+
+        assert!(activity.for_alias("alice").is_empty());
+        assert!(activity.awaiting_mint().is_empty());
+    }
+
+    #[test]
+    fn deposit_record_serialization() {
+        let record = deposit_record("alice", 0, SubmissionStatus::Failed("boom".to_string()));
+
+        let json = serde_json::to_value(&record).unwrap();
+
+        assert_eq!(json["status"], "failed");
+        assert_eq!(json["error"], "boom");
+        assert_eq!(json["txid"], Txid::all_zeros().to_string());
+    }
+
+    fn deposit_request(
+        deposit: &MonitoredDeposit,
+        amount: Amount,
+    ) -> (Transaction, CreateDepositRequestBody) {
+        let tx = Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn::default()],
+            output: vec![bitcoin::TxOut {
+                value: amount,
+                script_pubkey: deposit.to_script_pubkey(),
+            }],
+        };
+        let request = CreateDepositRequestBody {
+            bitcoin_tx_output_index: 0,
+            bitcoin_txid: tx.compute_txid().to_string(),
+            deposit_script: deposit
+                .deposit_script_inputs
+                .deposit_script()
+                .to_hex_string(),
+            reclaim_script: deposit
+                .reclaim_script_inputs
+                .reclaim_script()
+                .to_hex_string(),
+            transaction_hex: bitcoin::consensus::encode::serialize_hex(&tx),
+        };
+        (tx, request)
+    }
+
+    #[test]
+    fn verify_request_accepts_valid_request() {
+        let deposit = monitored_deposit("alice");
+        let amount = Amount::from_sat(100_000);
+        let (_, request) = deposit_request(&deposit, amount);
+
+        deposit.verify_request(&request, amount, false).unwrap();
+    }
+
+    #[test_case(|_, request| request.bitcoin_tx_output_index = 1; "wrong output index")]
+    #[test_case(|_, request| request.bitcoin_txid = Txid::all_zeros().to_string(); "wrong txid")]
+    #[test_case(|_, request| request.transaction_hex = "00".to_string(); "invalid transaction")]
+    #[test_case(|_, request| request.reclaim_script = "51".to_string(); "wrong reclaim script")]
+    #[test_case(|tx, request| {
+        tx.output[0].script_pubkey = ScriptBuf::new_op_return([]);
+        request.bitcoin_txid = tx.compute_txid().to_string();
+        request.transaction_hex = bitcoin::consensus::encode::serialize_hex(tx);
+    }; "wrong scriptPubKey")]
+    fn verify_request_rejects_invalid_request(
+        modify: fn(&mut Transaction, &mut CreateDepositRequestBody),
+    ) {
+        let deposit = monitored_deposit("alice");
+        let amount = Amount::from_sat(100_000);
+        let (mut tx, mut request) = deposit_request(&deposit, amount);
+        modify(&mut tx, &mut request);
+
+        let result = deposit.verify_request(&request, amount, false);
+
+        assert!(matches!(
+            result,
+            Err(Error::InvalidDepositRequest(alias, _)) if alias == "alice"
+        ));
+    }
+
+    #[test]
+    fn verify_request_rejects_other_deposit() {
+        let deposit = monitored_deposit("alice");
+        let amount = Amount::from_sat(100_000);
+        let (_, request) = deposit_request(&deposit, amount);
 
-        Ok(create_deposits)
+        assert!(
+            deposit
+                .verify_request(&request, Amount::from_sat(99_999), false)
+                .is_err()
+        );
+        assert!(
+            monitored_deposit("bob")
+                .verify_request(&request, amount, false)
+                .is_err()
+        );
     }
 }
@@ -0,0 +1,191 @@
+//! Module to build transactions funding monitored deposits
+
+use bitcoin::absolute::LockTime;
+use bitcoin::transaction::Version;
+use bitcoin::{Amount, OutPoint, Psbt, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness};
+
+use crate::deposit_monitor::MonitoredDeposit;
+use crate::error::Error;
+
+/// Create an unsigned PSBT paying `amount` to the `deposit` address.
+///
+/// The transaction spends all the `inputs`; what is left after the deposit
+/// amount and the `fee` is sent to `change_script`, unless it is dust, in
+/// which case it is added to the fee with a warning.
+pub fn create_deposit_psbt(
+    deposit: &MonitoredDeposit,
+    amount: Amount,
+    inputs: Vec<(OutPoint, TxOut)>,
+    change_script: Option<ScriptBuf>,
+    fee: Amount,
+) -> Result<Psbt, Error> {
+    let total = inputs
+        .iter()
+        .map(|(_, tx_out)| tx_out.value)
+        .try_fold(Amount::ZERO, Amount::checked_add)
+        .ok_or(Error::InvalidAmount)?;
+    let required = amount.checked_add(fee).ok_or(Error::InvalidAmount)?;
+    let change = total
+        .checked_sub(required)
+        .ok_or(Error::InsufficientFunds(total, required))?;
+
+    let mut output = vec![TxOut {
+        value: amount,
+        script_pubkey: deposit.to_script_pubkey(),
+    }];
+
+    if change > Amount::ZERO {
+        let script_pubkey = change_script.ok_or(Error::MissingChangeAddress(change))?;
+        if change >= script_pubkey.minimal_non_dust() {
+            output.push(TxOut { value: change, script_pubkey });
+        } else {
+            tracing::warn!(
+                %change,
+                fee = %fee.checked_add(change).unwrap_or(fee),
+                "the change is dust; adding it to the fee"
+            );
+        }
+    }
+
+    let tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: inputs
+            .iter()
+            .map(|(outpoint, _)| TxIn {
+                previous_output: *outpoint,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            })
+            .collect(),
+        output,
+    };
+
+    let mut psbt = Psbt::from_unsigned_tx(tx)?;
+
+    for (psbt_input, (outpoint, tx_out)) in psbt.inputs.iter_mut().zip(inputs) {
+        if tx_out.script_pubkey.is_witness_program() {
+            psbt_input.witness_utxo = Some(tx_out);
+        } else {
+            tracing::warn!(
+                %outpoint,
+                "funding input is not a segwit output; the signer must provide the previous transaction"
+            );
+        }
+    }
+
+    add_deposit_taproot_info(&mut psbt, deposit)?;
+
+    Ok(psbt)
+}
+
+/// Fill the taproot internal key and tree of the PSBT outputs paying to the
+/// `deposit` address, so that signers can verify the deposit scripts the
+/// output commits to.
+pub fn add_deposit_taproot_info(psbt: &mut Psbt, deposit: &MonitoredDeposit) -> Result<(), Error> {
+    let script_pubkey = deposit.to_script_pubkey();
+    let tap_tree = deposit.to_tap_tree()?;
+
+    let mut found = false;
+    for (tx_out, psbt_output) in psbt.unsigned_tx.output.iter().zip(psbt.outputs.iter_mut()) {
+        if tx_out.script_pubkey == script_pubkey {
+            psbt_output.tap_internal_key = Some(deposit.taproot_internal_key());
+            psbt_output.tap_tree = Some(tap_tree.clone());
+            found = true;
+        }
+    }
+
+    if !found {
+        return Err(Error::MissingDepositOutput(script_pubkey));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash as _;
+    use bitcoin::{Txid, WPubkeyHash};
+    use test_case::test_case;
+
+    use super::*;
+    use crate::testing::monitored_deposit;
+
+    fn funding_input(vout: u32, sats: u64) -> (OutPoint, TxOut) {
+        let tx_out = TxOut {
+            value: Amount::from_sat(sats),
+            script_pubkey: ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros()),
+        };
+        (OutPoint::new(Txid::all_zeros(), vout), tx_out)
+    }
+
+    #[test_case(&[100_000], 1; "exact amount")]
+    #[test_case(&[100_000, 50_000], 2; "with change")]
+    #[test_case(&[100_100], 1; "dust change")]
+    fn create_deposit_psbt_works(inputs: &[u64], expected_outputs: usize) {
+        let deposit = monitored_deposit("test");
+        let inputs = inputs
+            .iter()
+            .enumerate()
+            .map(|(vout, sats)| funding_input(vout as u32, *sats))
+            .collect::<Vec<_>>();
+        let change_script = ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros());
+
+        let psbt = create_deposit_psbt(
+            &deposit,
+            Amount::from_sat(99_000),
+            inputs.clone(),
+            Some(change_script.clone()),
+            Amount::from_sat(1_000),
+        )
+        .unwrap();
+
+        assert_eq!(psbt.unsigned_tx.input.len(), inputs.len());
+        assert_eq!(psbt.unsigned_tx.output.len(), expected_outputs);
+        assert!(psbt.inputs.iter().all(|input| input.witness_utxo.is_some()));
+
+        let deposit_output = &psbt.unsigned_tx.output[0];
+        assert_eq!(deposit_output.script_pubkey, deposit.to_script_pubkey());
+        assert_eq!(deposit_output.value, Amount::from_sat(99_000));
+        assert_eq!(
+            psbt.outputs[0].tap_internal_key,
+            Some(deposit.taproot_internal_key())
+        );
+        assert_eq!(
+            psbt.outputs[0].tap_tree,
+            Some(deposit.to_tap_tree().unwrap())
+        );
+
+        if expected_outputs == 2 {
+            assert_eq!(psbt.unsigned_tx.output[1].script_pubkey, change_script);
+            assert!(psbt.outputs[1].tap_tree.is_none());
+        }
+    }
+
+    #[test]
+    fn create_deposit_psbt_insufficient_funds() {
+        let result = create_deposit_psbt(
+            &monitored_deposit("test"),
+            Amount::from_sat(99_000),
+            vec![funding_input(0, 99_500)],
+            None,
+            Amount::from_sat(1_000),
+        );
+
+        assert!(matches!(result, Err(Error::InsufficientFunds(_, _))));
+    }
+
+    #[test]
+    fn create_deposit_psbt_requires_change_address() {
+        let result = create_deposit_psbt(
+            &monitored_deposit("test"),
+            Amount::from_sat(99_000),
+            vec![funding_input(0, 200_000)],
+            None,
+            Amount::from_sat(1_000),
+        );
+
+        assert!(matches!(result, Err(Error::MissingChangeAddress(_))));
+    }
+}
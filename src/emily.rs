@@ -0,0 +1,281 @@
+//! Emily API client, spreading the requests over the configured endpoints
+
+use std::sync::Arc;
+
+use emily_client::apis::configuration::Configuration as EmilyConfig;
+use emily_client::apis::{deposit_api, limits_api};
+use emily_client::models::{CreateDepositRequestBody, Deposit, Limits};
+use tokio::task::JoinSet;
+
+use crate::config::EmilyPolicy;
+use crate::error::Error;
+
+/// The outcome of a request to a single Emily endpoint
+#[derive(Debug)]
+pub struct EndpointOutcome {
+    /// The endpoint base path
+    pub endpoint: String,
+    /// The request result
+    pub result: Result<(), Error>,
+}
+
+/// Emily API client over one or more endpoints
+#[derive(Debug, Clone)]
+pub struct EmilyClient {
+    endpoints: Arc<Vec<EmilyConfig>>,
+    policy: EmilyPolicy,
+}
+
+impl EmilyClient {
+    /// Create a new client; the endpoints are tried in the given order
+    pub fn new(endpoints: Vec<EmilyConfig>, policy: EmilyPolicy) -> Self {
+        Self {
+            endpoints: Arc::new(endpoints),
+            policy,
+        }
+    }
+
+    /// Get the endpoints configs
+    pub fn endpoints(&self) -> &[EmilyConfig] {
+        &self.endpoints
+    }
+
+    /// Get the policy used to create deposits
+    pub fn policy(&self) -> EmilyPolicy {
+        self.policy
+    }
+
+    /// Create the deposit, following the policy: with `failover` the
+    /// endpoints are tried in order until one succeeds, with `broadcast` the
+    /// deposit is sent to all of them concurrently. The outcomes are returned
+    /// in the endpoints order.
+    pub async fn create_deposit(&self, deposit: &CreateDepositRequestBody) -> Vec<EndpointOutcome> {
+        match self.policy {
+            EmilyPolicy::Failover => {
+                let mut outcomes = Vec::new();
+                for config in self.endpoints.iter() {
+                    let result = create_deposit(config, deposit.clone()).await;
+                    let succeeded = result.is_ok();
+                    outcomes.push(EndpointOutcome {
+                        endpoint: config.base_path.clone(),
+                        result,
+                    });
+                    if succeeded {
+                        break;
+                    }
+                }
+                outcomes
+            }
+            EmilyPolicy::Broadcast => {
+                let mut tasks = JoinSet::new();
+                for (index, config) in self.endpoints.iter().enumerate() {
+                    let config = config.clone();
+                    let deposit = deposit.clone();
+                    tasks.spawn(async move {
+                        let result = create_deposit(&config, deposit).await;
+                        (index, config.base_path, result)
+                    });
+                }
+
+                let mut outcomes = Vec::with_capacity(self.endpoints.len());
+                while let Some(joined) = tasks.join_next().await {
+                    match joined {
+                        Ok(outcome) => outcomes.push(outcome),
+                        Err(error) => tracing::error!(%error, "emily request task failed"),
+                    }
+                }
+                outcomes.sort_by_key(|(index, _, _)| *index);
+                outcomes
+                    .into_iter()
+                    .map(|(_, endpoint, result)| EndpointOutcome { endpoint, result })
+                    .collect()
+            }
+        }
+    }
+
+    /// Get the deposit from the first endpoint knowing it, or `None` if all
+    /// the endpoints answered that they do not
+    pub async fn get_deposit(&self, txid: &str, vout: &str) -> Result<Option<Deposit>, Error> {
+        let mut last_error = None;
+        for config in self.endpoints.iter() {
+            match deposit_api::get_deposit(config, txid, vout).await {
+                Ok(deposit) => return Ok(Some(deposit)),
+                // Endpoints may lag behind each other, so try the next ones
+                Err(emily_client::apis::Error::ResponseError(response))
+                    if response.status == reqwest::StatusCode::NOT_FOUND =>
+                {
+                    tracing::debug!(endpoint = %config.base_path, "deposit not found");
+                }
+                Err(error) => {
+                    tracing::debug!(%error, endpoint = %config.base_path, "cannot get deposit");
+                    last_error = Some(emily_error(config, error));
+                }
+            }
+        }
+
+        match last_error {
+            Some(error) => Err(error),
+            None if self.endpoints.is_empty() => Err(no_endpoint_error()),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the limits from the first endpoint answering
+    pub async fn get_limits(&self) -> Result<Limits, Error> {
+        let mut last_error = None;
+        for config in self.endpoints.iter() {
+            match limits_api::get_limits(config).await {
+                Ok(limits) => return Ok(limits),
+                Err(error) => {
+                    tracing::debug!(%error, endpoint = %config.base_path, "cannot get limits");
+                    last_error = Some(emily_error(config, error));
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(no_endpoint_error))
+    }
+}
+
+async fn create_deposit(
+    config: &EmilyConfig,
+    deposit: CreateDepositRequestBody,
+) -> Result<(), Error> {
+    deposit_api::create_deposit(config, deposit)
+        .await
+        .map(|_| ())
+        .map_err(|error| emily_error(config, error))
+}
+
+fn emily_error(config: &EmilyConfig, error: impl std::fmt::Display) -> Error {
+    Error::Emily(config.base_path.clone(), error.to_string())
+}
+
+fn no_endpoint_error() -> Error {
+    Error::Emily(String::new(), "no endpoint configured".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn deposit() -> CreateDepositRequestBody {
+        CreateDepositRequestBody {
+            bitcoin_txid: "00".repeat(32),
+            ..Default::default()
+        }
+    }
+
+    fn created() -> String {
+        serde_json::to_string(&Deposit::default()).unwrap()
+    }
+
+    fn config(server: &mockito::Server) -> EmilyConfig {
+        EmilyConfig {
+            base_path: server.url(),
+            ..Default::default()
+        }
+    }
+
+    #[test_case(EmilyPolicy::Failover, 0; "failover stops at the first success")]
+    #[test_case(EmilyPolicy::Broadcast, 1; "broadcast sends to all")]
+    #[tokio::test]
+    async fn create_deposit_follows_policy(policy: EmilyPolicy, backup_hits: usize) {
+        let mut primary = mockito::Server::new_async().await;
+        let mut backup = mockito::Server::new_async().await;
+        let primary_mock = primary
+            .mock("POST", "/deposit")
+            .with_status(201)
+            .with_body(created())
+            .expect(1)
+            .create_async()
+            .await;
+        let backup_mock = backup
+            .mock("POST", "/deposit")
+            .with_status(201)
+            .with_body(created())
+            .expect(backup_hits)
+            .create_async()
+            .await;
+
+        let client = EmilyClient::new(vec![config(&primary), config(&backup)], policy);
+        let outcomes = client.create_deposit(&deposit()).await;
+
+        assert_eq!(outcomes.len(), 1 + backup_hits);
+        assert_eq!(outcomes[0].endpoint, primary.url());
+        primary_mock.assert_async().await;
+        backup_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn create_deposit_fails_over() {
+        let mut primary = mockito::Server::new_async().await;
+        let mut backup = mockito::Server::new_async().await;
+        let primary_mock = primary
+            .mock("POST", "/deposit")
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+        let backup_mock = backup
+            .mock("POST", "/deposit")
+            .with_status(201)
+            .with_body(created())
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = EmilyClient::new(
+            vec![config(&primary), config(&backup)],
+            EmilyPolicy::Failover,
+        );
+        let outcomes = client.create_deposit(&deposit()).await;
+
+        assert!(outcomes[0].result.is_err());
+        assert!(outcomes[1].result.is_ok());
+        primary_mock.assert_async().await;
+        backup_mock.assert_async().await;
+    }
+
+    #[test_case(404, true; "found on the backup")]
+    #[test_case(404, false; "not found anywhere")]
+    #[test_case(503, false; "backup not found, primary failed")]
+    #[tokio::test]
+    async fn get_deposit_tries_all_endpoints(primary_status: usize, backup_has_it: bool) {
+        let mut primary = mockito::Server::new_async().await;
+        let mut backup = mockito::Server::new_async().await;
+        let path = format!("/deposit/{}/0", "00".repeat(32));
+        let primary_mock = primary
+            .mock("GET", path.as_str())
+            .with_status(primary_status)
+            .expect(1)
+            .create_async()
+            .await;
+        let backup_mock = if backup_has_it {
+            backup
+                .mock("GET", path.as_str())
+                .with_status(200)
+                .with_body(created())
+        } else {
+            backup.mock("GET", path.as_str()).with_status(404)
+        }
+        .expect(1)
+        .create_async()
+        .await;
+
+        let client = EmilyClient::new(
+            vec![config(&primary), config(&backup)],
+            EmilyPolicy::Failover,
+        );
+        let result = client.get_deposit(&"00".repeat(32), "0").await;
+
+        match (primary_status, backup_has_it) {
+            (_, true) => assert!(result.unwrap().is_some()),
+            (404, false) => assert!(result.unwrap().is_none()),
+            _ => assert!(result.is_err()),
+        }
+        primary_mock.assert_async().await;
+        backup_mock.assert_async().await;
+    }
+}
@@ -1,12 +1,21 @@
 //! Top-level error type
 
 use std::borrow::Cow;
+use std::path::PathBuf;
 
-use bitcoin::ScriptBuf;
+use bitcoin::{Amount, OutPoint, ScriptBuf};
 
 /// Top-level application error
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
+    /// The admin API server failed
+    #[error("admin API server error: {0}")]
+    AdminApiServer(#[source] std::io::Error),
+
+    /// The admin API state file cannot be read, parsed or written
+    #[error("invalid admin API state file {0}: {1}")]
+    AdminApiStateFile(PathBuf, String),
+
     /// Error from the Bitcoin RPC client.
     #[error("bitcoin RPC error: {0}")]
     BitcoinCoreRpc(#[from] bitcoincore_rpc::Error),
@@ -15,10 +24,81 @@ pub enum Error {
     #[error("could not create RPC client to {1}: {0}")]
     BitcoinCoreRpcClient(#[source] bitcoincore_rpc::Error, String),
 
+    /// The bitcoin node runs on a different network than the configured one
+    #[error("the bitcoin node runs on {0}, but {1} is configured")]
+    BitcoinNetworkMismatch(bitcoin::Network, bitcoin::Network),
+
+    /// A Clarity value cannot be serialized or deserialized
+    #[error("clarity error: {0}")]
+    Clarity(String),
+
+    /// Invalid configuration
+    #[error("invalid configuration: {0}")]
+    Config(#[from] crate::config::error::SpoxConfigError),
+
+    /// The configuration check found some problems
+    #[error("found {0} problems in the configuration")]
+    ConfigCheckFailed(usize),
+
+    /// The deposit amount is above the per-deposit cap
+    #[error("the deposit amount of {0} sats is above the per-deposit cap of {1} sats")]
+    DepositAboveCap(u64, u64),
+
+    /// The deposit amount is below the per-deposit minimum
+    #[error("the deposit amount of {0} sats is below the per-deposit minimum of {1} sats")]
+    DepositBelowMinimum(u64, u64),
+
     /// The pending deposit is expired
     #[error("the pending deposit is expired")]
     DepositExpired,
 
+    /// The per-deposit minimum is above the per-deposit cap
+    #[error(
+        "the per-deposit minimum of {0} sats is above the per-deposit cap of {1} sats, so no deposit is within the limits"
+    )]
+    DepositMinimumAboveCap(u64, u64),
+
+    /// The dry-run output cannot be written
+    #[error("cannot write the dry-run output: {0}")]
+    DryRunOutput(#[source] std::io::Error),
+
+    /// The dry-run output is set without a dry run
+    #[error("--dry-run-output requires --dry-run")]
+    DryRunOutputWithoutDryRun,
+
+    /// A monitored deposit with the same alias or scriptPubKey already exists
+    #[error("a monitored deposit with the same alias or scriptPubKey already exists: {0}")]
+    DuplicateMonitoredDeposit(String),
+
+    /// An Emily request failed
+    #[error("emily request to {0} failed: {1}")]
+    Emily(String, String),
+
+    /// The funding inputs do not cover the deposit amount and fee
+    #[error("insufficient funds: inputs total {0}, required {1}")]
+    InsufficientFunds(Amount, Amount),
+
+    /// The scriptPubKey cannot be encoded as an address
+    #[error("cannot encode the scriptPubKey as an address: {0}")]
+    InvalidAddress(#[source] bitcoin::address::FromScriptError),
+
+    /// An amount overflowed
+    #[error("invalid amount")]
+    InvalidAmount,
+
+    /// The create deposit request does not match the monitored deposit, once
+    /// parsed the way sBTC signers do
+    #[error("invalid deposit request for {0}: {1}")]
+    InvalidDepositRequest(String, String),
+
+    /// Error when parsing a PSBT
+    #[error("invalid PSBT: {0}")]
+    InvalidPsbt(#[source] bitcoin::psbt::PsbtParseError),
+
+    /// The deposit taproot tree does not match the deposit scriptPubKey
+    #[error("cannot build a taproot tree matching the deposit scriptPubKey")]
+    InvalidTaprootTree,
+
     /// This occurs when converting a byte slice to a secp256k1::PublicKey.
     #[error("invalid public key: {0}")]
     InvalidPublicKey(#[source] bitcoin::key::FromSliceError),
@@ -31,6 +111,12 @@ pub enum Error {
     #[error("could not parse the provided URL: {0}")]
     InvalidUrl(#[source] url::ParseError),
 
+    /// The deposit max fee leaves nothing to mint within the per-deposit cap
+    #[error(
+        "the max fee of {0} sats is not below the per-deposit cap of {1} sats, so no deposit within the cap can pay it"
+    )]
+    MaxFeeAboveDepositCap(u64, u64),
+
     /// No chain tip found.
     #[error("no bitcoin chain tip")]
     NoChainTip,
@@ -43,6 +129,23 @@ pub enum Error {
     #[error("missing monitored deposit address for scriptPubKey {0}")]
     MissingMonitoredDeposit(ScriptBuf),
 
+    /// The funding inputs exceed the deposit amount and fee, but no change
+    /// address was provided
+    #[error("inputs exceed the deposit amount and fee by {0}, but no change address was provided")]
+    MissingChangeAddress(Amount),
+
+    /// The transaction has no output paying to the deposit scriptPubKey
+    #[error("the transaction has no output paying to the deposit scriptPubKey {0}")]
+    MissingDepositOutput(ScriptBuf),
+
+    /// The funding UTXO does not exist or is already spent
+    #[error("funding UTXO {0} not found or already spent")]
+    MissingFundingUtxo(OutPoint),
+
+    /// The transaction has no output with the given index
+    #[error("transaction output {0} not found")]
+    MissingTxOutput(OutPoint),
+
     /// Missing stacks configuration
     #[error("missing stacks configuration")]
     MissingStacksConfig,
@@ -55,6 +158,14 @@ pub enum Error {
     #[error("a port must be specified")]
     PortRequired,
 
+    /// Error when building a PSBT
+    #[error("PSBT error: {0}")]
+    Psbt(#[from] bitcoin::psbt::Error),
+
+    /// A read-only contract call returned an error
+    #[error("read-only call to {0} failed: {1}")]
+    ReadOnlyCallFailed(String, String),
+
     /// Reqwest error
     #[error(transparent)]
     Reqwest(#[from] reqwest::Error),
@@ -63,10 +174,28 @@ pub enum Error {
     #[error(transparent)]
     Sbtc(#[from] sbtc::error::Error),
 
+    /// The UTXO set scan chunks were answered at different chain tips
+    #[error("the utxo set scan saw different chain tips: {0} and {1}")]
+    ScanTipMismatch(bitcoin::BlockHash, bitcoin::BlockHash),
+
     /// A call to `scantxoutset` failed
     #[error("a call to `scantxoutset` failed")]
     ScanTxOutFailure,
 
+    /// The transaction output does not pay to the monitored deposit address
+    #[error("the output {0} does not pay to the deposit address of {1}")]
+    ScriptPubKeyMismatch(OutPoint, String),
+
+    /// JSON serialization error
+    #[error("JSON error: {0}")]
+    SerdeJson(#[from] serde_json::Error),
+
+    /// The signer data and the checked aggregate key disagree
+    #[error(
+        "the aggregate key of the signer data does not match the one checked against the stacks endpoints"
+    )]
+    SignersAggregateKeyMismatch,
+
     /// Could not make a successful request to the Stacks node.
     #[error("failed to make a request to the stacks Node: {0}")]
     StacksNodeRequest(#[source] reqwest::Error),
@@ -75,7 +204,31 @@ pub enum Error {
     #[error("received a non success status code response from a stacks node: {0}")]
     StacksNodeResponse(#[source] reqwest::Error),
 
+    /// Not enough Stacks endpoints agree on a value
+    #[error("{1} Stacks endpoints agree, but a quorum of {0} is required")]
+    StacksQuorumNotReached(usize, usize),
+
+    /// Some deposits could not be submitted to Emily
+    #[error("failed to submit {0} deposits to emily")]
+    SubmissionFailed(usize),
+
+    /// The TLS configuration cannot be built
+    #[error("invalid TLS configuration: {0}")]
+    Tls(String),
+
+    /// A TLS certificate or key file cannot be read or parsed
+    #[error("invalid TLS file {0}: {1}")]
+    TlsFile(PathBuf, String),
+
     /// Reqwest error
     #[error("response from stacks node did not conform to the expected schema: {0}")]
     UnexpectedStacksResponse(#[source] reqwest::Error),
+
+    /// The transaction is not confirmed in the canonical chain
+    #[error("transaction {0} is not confirmed in the canonical chain")]
+    UnconfirmedTransaction(bitcoin::Txid),
+
+    /// The alias is not in the monitored deposits
+    #[error("unknown monitored deposit alias: {0}")]
+    UnknownAlias(String),
 }
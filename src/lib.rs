@@ -1,13 +1,21 @@
 #![doc = include_str!("../README.md")]
 #![deny(missing_docs)]
 
+pub mod admin_api;
 pub mod bitcoin;
+pub mod check;
 pub mod config;
 pub mod context;
 pub mod deposit_monitor;
+pub mod deposit_tx;
+pub mod emily;
 pub mod error;
+pub mod limits;
 pub mod logging;
+pub mod notifier;
+pub mod proxy;
 pub mod stacks;
+pub mod tls;
 
 #[cfg(any(test, feature = "testing"))]
 pub mod testing;
@@ -0,0 +1,106 @@
+//! sBTC deposit limits: the signers do not process deposits outside them
+
+use emily_client::models::Limits;
+
+use crate::error::Error;
+
+/// The per-deposit limits, in sats; `None` if unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DepositLimits {
+    /// The minimum deposit amount
+    pub minimum: Option<u64>,
+    /// The maximum deposit amount
+    pub cap: Option<u64>,
+}
+
+impl From<&Limits> for DepositLimits {
+    fn from(limits: &Limits) -> Self {
+        Self {
+            minimum: limits.per_deposit_minimum.flatten(),
+            cap: limits.per_deposit_cap.flatten(),
+        }
+    }
+}
+
+impl DepositLimits {
+    /// Check that a deposit amount is within the limits
+    pub fn check_amount(&self, amount: u64) -> Result<(), Error> {
+        if let Some(minimum) = self.minimum {
+            if amount < minimum {
+                return Err(Error::DepositBelowMinimum(amount, minimum));
+            }
+        }
+        if let Some(cap) = self.cap {
+            if amount > cap {
+                return Err(Error::DepositAboveCap(amount, cap));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that a deposit `max_fee` can be paid by a deposit within the
+    /// limits, i.e. that some amount not below the minimum and not above the
+    /// cap is above the max fee.
+    pub fn check_max_fee(&self, max_fee: u64) -> Result<(), Error> {
+        let Some(cap) = self.cap else {
+            return Ok(());
+        };
+        if max_fee >= cap {
+            return Err(Error::MaxFeeAboveDepositCap(max_fee, cap));
+        }
+        match self.minimum {
+            Some(minimum) if minimum > cap => Err(Error::DepositMinimumAboveCap(minimum, cap)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    const LIMITS: DepositLimits = DepositLimits {
+        minimum: Some(10_000),
+        cap: Some(1_000_000),
+    };
+
+    #[test]
+    fn limits_from_emily() {
+        let limits: Limits = serde_json::from_value(serde_json::json!({
+            "perDepositMinimum": 10_000,
+            "perDepositCap": null,
+            "accountCaps": {},
+        }))
+        .unwrap();
+
+        assert_eq!(
+            DepositLimits::from(&limits),
+            DepositLimits {
+                minimum: Some(10_000),
+                cap: None,
+            }
+        );
+    }
+
+    #[test_case(10_000, true; "minimum")]
+    #[test_case(1_000_000, true; "cap")]
+    #[test_case(9_999, false; "below minimum")]
+    #[test_case(1_000_001, false; "above cap")]
+    fn check_amount(amount: u64, valid: bool) {
+        assert_eq!(LIMITS.check_amount(amount).is_ok(), valid);
+        assert!(DepositLimits::default().check_amount(amount).is_ok());
+    }
+
+    #[test_case(LIMITS, 5_000, true; "below minimum")]
+    #[test_case(LIMITS, 20_000, true; "below cap")]
+    #[test_case(LIMITS, 1_000_000, false; "at cap")]
+    #[test_case(DepositLimits { minimum: Some(10_000), cap: None }, 1_000_000, true; "no cap")]
+    #[test_case(DepositLimits { minimum: Some(20_000), cap: Some(10_000) }, 5_000, false; "minimum above cap")]
+    fn check_max_fee(limits: DepositLimits, max_fee: u64, valid: bool) {
+        assert_eq!(limits.check_max_fee(max_fee).is_ok(), valid);
+        assert!(DepositLimits::default().check_max_fee(max_fee).is_ok());
+    }
+}
@@ -1,14 +1,28 @@
-use std::path::PathBuf;
-use std::time::Duration;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr as _;
+use std::time::{Duration, Instant};
 
-use bitcoin::Address;
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::secp256k1::SECP256K1;
+use bitcoin::{Address, Amount, BlockHash, OutPoint, ScriptBuf, Txid};
 use clap::{Parser, Subcommand, ValueEnum};
-use emily_client::apis::deposit_api;
+use emily_client::models::CreateDepositRequestBody;
+use qrcode::QrCode;
+use qrcode::render::{svg, unicode};
+use spox::admin_api::AdminApi;
 use spox::bitcoin::BlockRef;
+use spox::bitcoin::node::BitcoinCoreClient;
 use spox::config::Settings;
 use spox::context::Context;
-use spox::deposit_monitor::{DepositMonitor, MonitoredDeposit};
+use spox::deposit_monitor::{
+    DepositAddressInfo, DepositMonitor, MonitoredDeposit, SubmissionStatus,
+};
+use spox::deposit_tx;
 use spox::error::Error;
+use spox::notifier::Event;
 use spox::stacks::node::StacksClient;
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
@@ -17,16 +31,162 @@ enum LogOutputFormat {
     Pretty,
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum DepositAddressFormat {
+    Text,
+    Json,
+    Csv,
+    Toml,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum QrFormat {
+    Terminal,
+    Png,
+    Svg,
+}
+
 #[derive(Debug, Clone, Parser)]
 struct GetDepositAddressArgs {
-    #[clap(short = 'n', long = "network", default_value = "bitcoin")]
-    pub network: bitcoin::Network,
+    #[clap(short = 'f', long = "format", default_value = "text")]
+    pub format: DepositAddressFormat,
+
+    /// Print BIP21 URIs, labelled with the alias, instead of the addresses
+    #[clap(long = "uri", conflicts_with = "format")]
+    pub uri: bool,
+
+    /// The amount to request in the BIP21 URIs, in sats
+    #[clap(long = "amount", requires = "uri")]
+    pub amount: Option<u64>,
+
+    /// Render a QR code of each address (or URI, with `--uri`)
+    #[clap(long = "qr", conflicts_with = "format")]
+    pub qr: Option<QrFormat>,
+
+    /// The directory where PNG and SVG QR codes are written, as `<alias>.<ext>`
+    #[clap(long = "qr-dir", default_value = ".")]
+    pub qr_dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Parser)]
+struct CreateDepositTxArgs {
+    /// The alias of the monitored deposit to fund
+    #[clap(short = 'a', long = "alias")]
+    pub alias: String,
+
+    /// The amount to deposit, in sats
+    #[clap(long = "amount")]
+    pub amount: u64,
+
+    /// A funding UTXO, as `<txid>:<vout>`; can be repeated
+    #[clap(long = "utxo", required_unless_present = "wallet")]
+    pub utxos: Vec<OutPoint>,
+
+    /// The address receiving the change, if any, of the funding UTXOs
+    #[clap(long = "change-address", conflicts_with = "wallet")]
+    pub change_address: Option<Address<NetworkUnchecked>>,
+
+    /// The transaction fee paid by the funding UTXOs, in sats; required
+    /// without a wallet, which estimates it instead
+    #[clap(
+        long = "fee",
+        required_unless_present = "wallet",
+        conflicts_with = "wallet",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub fee: Option<u64>,
+
+    /// A bitcoind wallet selecting the funding UTXOs and the change address
+    #[clap(short = 'w', long = "wallet", conflicts_with = "utxos")]
+    pub wallet: Option<String>,
+}
+
+/// Scan the monitored deposits once and submit the pending ones to Emily.
+///
+/// Exits with 0 if every submission succeeded, 2 if some failed, and 1 on
+/// any other error.
+#[derive(Debug, Clone, Parser)]
+struct ScanOnceArgs {
+    /// Print the create deposit requests as JSON lines instead of submitting
+    /// them
+    #[clap(long = "dry-run")]
+    pub dry_run: bool,
+}
+
+/// Submit a single deposit to Emily, e.g. one missed during an outage
+#[derive(Debug, Clone, Parser)]
+struct SubmitDepositArgs {
+    /// The deposit transaction id
+    #[clap(long = "txid")]
+    pub txid: Txid,
+
+    /// The deposit output index
+    #[clap(long = "vout")]
+    pub vout: u32,
+
+    /// The alias of the monitored deposit; detected from the output
+    /// scriptPubKey if missing
+    #[clap(short = 'a', long = "alias")]
+    pub alias: Option<String>,
+
+    /// The hash of the block confirming the transaction, required if the
+    /// bitcoin node has no transaction index
+    #[clap(long = "block-hash")]
+    pub block_hash: Option<BlockHash>,
+
+    /// Print the create deposit request as JSON instead of submitting it
+    #[clap(long = "dry-run")]
+    pub dry_run: bool,
+}
+
+/// Print the current signer set, from the `sbtc-registry` contract
+#[derive(Debug, Clone, Parser)]
+struct GetSignersInfoArgs {
+    /// Print the signer set as JSON
+    #[clap(long = "json")]
+    pub json: bool,
+}
+
+/// Query the `sbtc-registry` contract
+#[derive(Debug, Clone, Parser)]
+struct RegistryArgs {
+    #[command(subcommand)]
+    pub command: RegistryCommand,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum RegistryCommand {
+    /// Print the public keys of the current signer set, one per line
+    SignerSet,
+    /// Print how many signatures the current signer set requires
+    SignatureThreshold,
+    /// Print whether the signers completed the deposit
+    DepositStatus {
+        /// The deposit transaction id
+        #[clap(long = "txid")]
+        txid: Txid,
+
+        /// The deposit output index
+        #[clap(long = "vout")]
+        vout: u32,
+
+        /// Also print the deposit status on Emily
+        #[clap(long = "emily")]
+        emily: bool,
+    },
 }
 
 #[derive(Debug, Subcommand)]
 enum CliCommand {
     GetSignersXonlyKey,
+    GetSignersInfo(GetSignersInfoArgs),
     GetDepositAddress(GetDepositAddressArgs),
+    CreateDepositTx(CreateDepositTxArgs),
+    CheckConfig,
+    Status,
+    ScanOnce(ScanOnceArgs),
+    SubmitDeposit(SubmitDepositArgs),
+    Registry(RegistryArgs),
 }
 
 /// Command line arguments
@@ -43,50 +203,203 @@ struct Args {
 
     #[clap(short = 'o', long = "output-format", default_value = "pretty")]
     output_format: LogOutputFormat,
+
+    /// Detect deposits and build the create deposit requests, but never
+    /// submit them to Emily
+    #[clap(long = "dry-run")]
+    dry_run: bool,
+
+    /// Append the would-be create deposit requests to this JSONL file instead
+    /// of logging them
+    #[clap(long = "dry-run-output")]
+    dry_run_output: Option<PathBuf>,
+}
+
+/// The exit code of `scan-once` when some deposits failed
+const EXIT_PARTIAL_FAILURE: u8 = 2;
+
+/// The outcome of a round of deposit submissions
+#[derive(Debug, Default, Clone, Copy)]
+struct SubmissionSummary {
+    /// Deposits submitted to Emily, or printed in dry-run mode
+    submitted: usize,
+    /// Deposits that could not be built from their UTXO, or that Emily
+    /// failed to create
+    failed: usize,
+}
+
+/// How the create deposit requests are handled
+enum Submission {
+    /// Submit them to Emily
+    Emily,
+    /// Never submit them: write them as JSON lines to the output, if any, or
+    /// log them
+    DryRun(Option<Box<dyn Write>>),
+}
+
+impl Submission {
+    /// The submission for the command: a dry run if either the global or the
+    /// subcommand `--dry-run` is set. Dry runs append to `--dry-run-output`,
+    /// if any; otherwise `scan-once` and `submit-deposit` print to stdout and
+    /// the monitor logs the requests.
+    fn new(args: &Args) -> Result<Self, Error> {
+        let (subcommand_dry_run, one_shot) = match &args.command {
+            Some(CliCommand::ScanOnce(command)) => (command.dry_run, true),
+            Some(CliCommand::SubmitDeposit(command)) => (command.dry_run, true),
+            _ => (false, false),
+        };
+        if !args.dry_run && !subcommand_dry_run {
+            if args.dry_run_output.is_some() {
+                return Err(Error::DryRunOutputWithoutDryRun);
+            }
+            return Ok(Submission::Emily);
+        }
+
+        let output: Option<Box<dyn Write>> = match &args.dry_run_output {
+            Some(path) => Some(Box::new(
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(Error::DryRunOutput)?,
+            )),
+            None if one_shot => Some(Box::new(std::io::stdout())),
+            None => None,
+        };
+
+        Ok(Submission::DryRun(output))
+    }
+
+    fn is_dry_run(&self) -> bool {
+        matches!(self, Submission::DryRun(_))
+    }
 }
 
 async fn fetch_and_create_deposits(
     context: &Context,
     deposit_monitor: &mut DepositMonitor,
     chain_tip: &BlockRef,
-) -> Result<(), Error> {
-    let emily_config = context.emily_config();
+    submission: &mut Submission,
+) -> Result<SubmissionSummary, Error> {
+    deposit_monitor.refresh_limits().await;
+    let pending = deposit_monitor.get_pending_deposits(chain_tip)?;
 
-    let deposits = deposit_monitor.get_pending_deposits(chain_tip)?;
+    tracing::debug!(
+        count = pending.requests.len(),
+        failed = pending.failed,
+        "fetched pending deposits"
+    );
+    let mut summary = if pending.requests.is_empty() {
+        SubmissionSummary::default()
+    } else {
+        submit_deposits(context, deposit_monitor, pending.requests, submission).await?
+    };
+    summary.failed += pending.failed;
 
-    tracing::debug!(count = deposits.len(), "fetched pending deposits");
-    if deposits.is_empty() {
-        return Ok(());
-    }
+    Ok(summary)
+}
+
+async fn submit_deposits(
+    context: &Context,
+    deposit_monitor: &DepositMonitor,
+    deposits: Vec<CreateDepositRequestBody>,
+    submission: &mut Submission,
+) -> Result<SubmissionSummary, Error> {
+    let mut summary = SubmissionSummary::default();
 
     for deposit in deposits {
+        if let Submission::DryRun(output) = submission {
+            let request = serde_json::to_string(&deposit)?;
+            match output {
+                Some(output) => writeln!(output, "{request}")
+                    .and_then(|()| output.flush())
+                    .map_err(Error::DryRunOutput)?,
+                None => tracing::info!(
+                    txid = %deposit.bitcoin_txid,
+                    vout = %deposit.bitcoin_tx_output_index,
+                    %request,
+                    "dry run: would create deposit in emily"
+                ),
+            }
+            summary.submitted += 1;
+            continue;
+        }
+
+        let outpoint = Txid::from_str(&deposit.bitcoin_txid)
+            .map(|txid| OutPoint::new(txid, deposit.bitcoin_tx_output_index));
+
         // TODO: emily will nop for duplicates, but we shouldn't send them
-        if let Err(error) = deposit_api::create_deposit(emily_config, deposit.clone()).await {
-            tracing::warn!(
-                %error,
-                txid = %deposit.bitcoin_txid,
-                vout = %deposit.bitcoin_tx_output_index,
-                "cannot create deposit in emily"
-            );
+        let outcomes = context.emily().create_deposit(&deposit).await;
+        let mut errors = Vec::new();
+        for outcome in &outcomes {
+            match &outcome.result {
+                Ok(()) => tracing::info!(
+                    txid = %deposit.bitcoin_txid,
+                    vout = %deposit.bitcoin_tx_output_index,
+                    endpoint = %outcome.endpoint,
+                    "created deposit in emily"
+                ),
+                Err(error) => {
+                    tracing::warn!(
+                        %error,
+                        txid = %deposit.bitcoin_txid,
+                        vout = %deposit.bitcoin_tx_output_index,
+                        endpoint = %outcome.endpoint,
+                        "cannot create deposit in emily"
+                    );
+                    errors.push(error.to_string());
+                }
+            }
+        }
+
+        // The deposit is submitted if any endpoint accepted it; the others
+        // get it again at the next polling round while it is pending.
+        let status = if outcomes.iter().any(|outcome| outcome.result.is_ok()) {
+            summary.submitted += 1;
+            SubmissionStatus::Submitted
         } else {
-            tracing::info!(
-                txid = %deposit.bitcoin_txid,
-                vout = %deposit.bitcoin_tx_output_index,
-                "created deposit in emily"
-            );
+            summary.failed += 1;
+            SubmissionStatus::Failed(errors.join("; "))
+        };
+
+        if let Ok(outpoint) = outpoint {
+            let alias = deposit_monitor
+                .activity()
+                .get(&outpoint)
+                .map(|record| record.alias);
+            let event = match &status {
+                SubmissionStatus::Failed(error) => Event::SubmissionFailed {
+                    alias,
+                    txid: outpoint.txid,
+                    vout: outpoint.vout,
+                    error: error.clone(),
+                },
+                _ => Event::DepositSubmitted {
+                    alias,
+                    txid: outpoint.txid,
+                    vout: outpoint.vout,
+                },
+            };
+            context.notifier().notify(event);
+
+            deposit_monitor.activity().set_status(&outpoint, status);
         }
     }
 
-    Ok(())
+    Ok(summary)
 }
 
 async fn runloop(
     context: Context,
     deposit_monitor: &mut DepositMonitor,
     polling_interval: Duration,
+    submission: &mut Submission,
 ) {
     let bitcoin_client = context.bitcoin_client();
+    let stall_timeout = context.notifier().chain_tip_stall_timeout();
     let mut last_chain_tip = None;
+    let mut last_chain_tip_change = Instant::now();
+    let mut stall_notified = false;
 
     loop {
         if last_chain_tip.is_some() {
@@ -109,12 +422,25 @@ async fn runloop(
             .is_some_and(|last| last == &chain_tip);
 
         if is_last_chaintip {
+            let stalled_for = last_chain_tip_change.elapsed();
+            if stalled_for >= stall_timeout && !stall_notified {
+                tracing::warn!(%chain_tip, ?stalled_for, "chain tip stalled");
+                context.notifier().notify(Event::ChainTipStalled {
+                    block_height: chain_tip.block_height,
+                    block_hash: chain_tip.block_hash,
+                    stalled_for: stalled_for.as_secs(),
+                });
+                stall_notified = true;
+            }
             continue;
         }
 
+        last_chain_tip_change = Instant::now();
+        stall_notified = false;
+
         tracing::debug!(%chain_tip, "new block; processing pending deposits");
 
-        let _ = fetch_and_create_deposits(&context, deposit_monitor, &chain_tip)
+        let _ = fetch_and_create_deposits(&context, deposit_monitor, &chain_tip, submission)
             .await
             .inspect_err(|error| {
                 tracing::warn!(
@@ -123,6 +449,16 @@ async fn runloop(
                 )
             });
 
+        let _ = deposit_monitor
+            .update_mint_status(&chain_tip)
+            .await
+            .inspect_err(|error| {
+                tracing::warn!(
+                    %error,
+                    "error updating the deposits mint status"
+                )
+            });
+
         last_chain_tip = Some(chain_tip);
     }
 }
@@ -140,20 +476,565 @@ async fn get_signers_xonly_key(config: &Settings) -> Result<(), Box<dyn std::err
     Ok(())
 }
 
+/// The signer set, as audited by operators
+#[derive(Debug, serde::Serialize)]
+struct SignersInfo {
+    aggregate_key: Option<String>,
+    peg_wallet_script_pubkey: Option<String>,
+    peg_wallet_address: Option<String>,
+    signature_threshold: u64,
+    signer_principal: String,
+    signer_set: Vec<String>,
+}
+
+async fn fetch_signers_info(config: &Settings) -> Result<SignersInfo, Box<dyn std::error::Error>> {
+    let stacks_client = StacksClient::try_from(config)?;
+    let signer_data = stacks_client.get_current_signer_data().await?;
+
+    // The signer data is read from a single endpoint, so its aggregate key
+    // is checked against the one read with the `quorum` and
+    // `compare_proofs` settings.
+    let aggregate_key = stacks_client.get_current_signers_aggregate_key().await?;
+    if signer_data.aggregate_key != aggregate_key {
+        return Err(Box::new(Error::SignersAggregateKeyMismatch));
+    }
+
+    // The registry does not store the peg wallet: it is derived locally as
+    // the key spend only taproot output of the aggregate key, the output
+    // the signers sweep the deposits to.
+    let peg_wallet = signer_data
+        .aggregate_key
+        .map(|key| ScriptBuf::new_p2tr(SECP256K1, key, None));
+    Ok(SignersInfo {
+        aggregate_key: signer_data.aggregate_key.map(|key| key.to_string()),
+        peg_wallet_script_pubkey: peg_wallet.as_ref().map(ScriptBuf::to_hex_string),
+        peg_wallet_address: peg_wallet
+            .as_ref()
+            .map(|script| Address::from_script(script, config.network().to_bitcoin_network()))
+            .transpose()?
+            .map(|address| address.to_string()),
+        signature_threshold: signer_data.signature_threshold,
+        signer_principal: signer_data.signer_principal.to_string(),
+        signer_set: signer_data
+            .signer_set
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+    })
+}
+
+async fn get_signers_info(
+    config: &Settings,
+    args: &GetSignersInfoArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let info = fetch_signers_info(config).await?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
+
+    let none = || "none".to_string();
+    println!("aggregate key: {}", info.aggregate_key.unwrap_or_else(none));
+    println!(
+        "peg wallet scriptPubKey (derived): {}",
+        info.peg_wallet_script_pubkey.unwrap_or_else(none)
+    );
+    println!(
+        "peg wallet address (derived): {}",
+        info.peg_wallet_address.unwrap_or_else(none)
+    );
+    println!("signer principal: {}", info.signer_principal);
+    println!(
+        "signature threshold: {} of {}",
+        info.signature_threshold,
+        info.signer_set.len()
+    );
+    println!("signer set:");
+    for public_key in info.signer_set {
+        println!("  {public_key}");
+    }
+
+    Ok(())
+}
+
+async fn registry(
+    config: &Settings,
+    args: &RegistryArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let stacks_client = StacksClient::try_from(config)?;
+
+    match &args.command {
+        RegistryCommand::SignerSet => {
+            let signer_data = stacks_client.get_current_signer_data().await?;
+            for public_key in signer_data.signer_set {
+                println!("{public_key}");
+            }
+        }
+        RegistryCommand::SignatureThreshold => {
+            let signer_data = stacks_client.get_current_signer_data().await?;
+            println!("{}", signer_data.signature_threshold);
+        }
+        RegistryCommand::DepositStatus { txid, vout, emily } => {
+            if *emily {
+                let context = Context::try_from(config)?;
+                match context
+                    .emily()
+                    .get_deposit(&txid.to_string(), &vout.to_string())
+                    .await?
+                {
+                    Some(emily_deposit) => println!("emily: {}", emily_deposit.status),
+                    None => println!("emily: not found"),
+                }
+            }
+            let status = stacks_client
+                .get_deposit_status(&OutPoint::new(*txid, *vout))
+                .await?;
+            match status {
+                Some(true) => println!("completed"),
+                Some(false) => println!("not completed"),
+                None => println!("unknown"),
+            }
+        }
+    }
+
+    Ok(())
+}
+
 async fn get_deposit_address(
+    config: &Settings,
     monitored: &[MonitoredDeposit],
     args: &GetDepositAddressArgs,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    for deposit in monitored {
-        let address = Address::from_script(&deposit.to_script_pubkey(), args.network)?;
-        println!("{}: {}", deposit.alias, address);
+    let network = config.network().to_bitcoin_network();
+    let mut infos = monitored
+        .iter()
+        .map(|deposit| deposit.to_address_info(network))
+        .collect::<Result<Vec<_>, Error>>()?;
+    infos.sort_by(|a, b| a.alias.cmp(&b.alias));
+
+    match args.format {
+        DepositAddressFormat::Text => {
+            for info in infos {
+                let payload = if args.uri {
+                    info.to_bip21_uri(args.amount.map(Amount::from_sat))
+                } else {
+                    info.address.clone()
+                };
+                println!("{}: {}", info.alias, payload);
+
+                if let Some(qr_format) = args.qr {
+                    render_qr_code(&info.alias, &payload, qr_format, &args.qr_dir)?;
+                }
+            }
+        }
+        DepositAddressFormat::Json => println!("{}", serde_json::to_string_pretty(&infos)?),
+        DepositAddressFormat::Csv => {
+            println!(
+                "alias,address,script_pubkey,deposit_script,reclaim_script,taproot_internal_key,recipient,max_fee,lock_time"
+            );
+            for info in infos {
+                println!(
+                    "{},{},{},{},{},{},{},{},{}",
+                    csv_field(&info.alias),
+                    info.address,
+                    info.script_pubkey,
+                    info.deposit_script,
+                    info.reclaim_script,
+                    info.taproot_internal_key,
+                    csv_field(&info.recipient),
+                    info.max_fee,
+                    info.lock_time
+                );
+            }
+        }
+        DepositAddressFormat::Toml => {
+            #[derive(serde::Serialize)]
+            struct TomlExport {
+                deposit: Vec<DepositAddressInfo>,
+            }
+            print!("{}", toml::to_string(&TomlExport { deposit: infos })?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Render `payload` as a QR code, either on the terminal or in a file named
+/// after the alias
+fn render_qr_code(
+    alias: &str,
+    payload: &str,
+    format: QrFormat,
+    dir: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let code = QrCode::new(payload)?;
+
+    match format {
+        QrFormat::Terminal => {
+            let rendered = code
+                .render::<unicode::Dense1x2>()
+                .dark_color(unicode::Dense1x2::Light)
+                .light_color(unicode::Dense1x2::Dark)
+                .build();
+            println!("{rendered}");
+        }
+        QrFormat::Png => {
+            let path = dir.join(qr_file_name(alias, "png"));
+            code.render::<image::Luma<u8>>().build().save(&path)?;
+            tracing::info!(path = %path.display(), "written QR code");
+        }
+        QrFormat::Svg => {
+            let path = dir.join(qr_file_name(alias, "svg"));
+            let rendered = code.render::<svg::Color>().min_dimensions(256, 256).build();
+            std::fs::write(&path, rendered)?;
+            tracing::info!(path = %path.display(), "written QR code");
+        }
+    }
+
+    Ok(())
+}
+
+/// The QR code file name of a deposit: the characters of the alias that are
+/// not ASCII alphanumerics, `-` or `_`, e.g. path separators and dots from
+/// contract principals, are replaced with `_` so that the file stays in the
+/// output directory
+fn qr_file_name(alias: &str, extension: &str) -> String {
+    let stem = alias
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!("{stem}.{extension}")
+}
+
+/// Quote a CSV field if it contains a separator, a quote or a newline
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+async fn create_deposit_tx(
+    config: &Settings,
+    monitored: &[MonitoredDeposit],
+    args: &CreateDepositTxArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let deposit = monitored
+        .iter()
+        .find(|deposit| deposit.alias == args.alias)
+        .ok_or_else(|| Error::UnknownAlias(args.alias.clone()))?;
+    let amount = Amount::from_sat(args.amount);
+    let network = config.network().to_bitcoin_network();
+
+    let psbt = match &args.wallet {
+        Some(wallet) => {
+            let bitcoin_client = BitcoinCoreClient::try_with_wallet(
+                &config.bitcoin_rpc_endpoint,
+                wallet,
+                &config.tls,
+            )?;
+            let address = Address::from_script(&deposit.to_script_pubkey(), network)?;
+
+            let mut psbt = bitcoin_client.wallet_create_funded_psbt(&address, amount)?;
+            deposit_tx::add_deposit_taproot_info(&mut psbt, deposit)?;
+            psbt
+        }
+        None => {
+            let bitcoin_client =
+                BitcoinCoreClient::with_tls(&config.bitcoin_rpc_endpoint, &config.tls)?;
+            let inputs = args
+                .utxos
+                .iter()
+                .map(|outpoint| {
+                    bitcoin_client
+                        .get_tx_out(outpoint)?
+                        .map(|tx_out| (*outpoint, tx_out))
+                        .ok_or(Error::MissingFundingUtxo(*outpoint))
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            let change_script = args
+                .change_address
+                .clone()
+                .map(|address| address.require_network(network))
+                .transpose()?
+                .map(|address| address.script_pubkey());
+
+            let fee = args
+                .fee
+                .map(Amount::from_sat)
+                .ok_or("--fee is required without --wallet")?;
+
+            deposit_tx::create_deposit_psbt(deposit, amount, inputs, change_script, fee)?
+        }
+    };
+
+    println!("{psbt}");
+    Ok(())
+}
+
+async fn check_config(config: &Settings) -> Result<(), Box<dyn std::error::Error>> {
+    let issues = spox::check::check_config(config).await;
+
+    for issue in &issues {
+        println!("{issue}");
+    }
+
+    if !issues.is_empty() {
+        return Err(Box::new(Error::ConfigCheckFailed(issues.len())));
+    }
+
+    println!("configuration is valid");
+    Ok(())
+}
+
+async fn status(
+    config: &Settings,
+    monitored: &[MonitoredDeposit],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let context = Context::try_from(config)?;
+
+    let chain_tip = context.bitcoin_client().get_chain_tip()?;
+    let script_pubkeys = monitored
+        .iter()
+        .map(MonitoredDeposit::to_script_pubkey)
+        .collect::<Vec<_>>();
+
+    let mut utxos_by_script = HashMap::<_, Vec<_>>::new();
+    for utxo in context.utxo_scanner().get_utxos(&script_pubkeys)? {
+        utxos_by_script
+            .entry(utxo.script_pub_key.clone())
+            .or_default()
+            .push(utxo);
+    }
+
+    let mut rows = Vec::new();
+    for (deposit, script_pubkey) in monitored.iter().zip(&script_pubkeys) {
+        let mut utxos = utxos_by_script.remove(script_pubkey).unwrap_or_default();
+        if utxos.is_empty() {
+            rows.push([
+                deposit.alias.clone(),
+                "-".into(),
+                "-".into(),
+                "-".into(),
+                "-".into(),
+                "no utxos".into(),
+            ]);
+            continue;
+        }
+        utxos.sort_by_key(|utxo| (utxo.block_height, utxo.txid, utxo.vout));
+
+        for utxo in utxos {
+            let unlock_in = deposit
+                .reclaim_unlock_height(utxo.block_height)
+                .saturating_sub(chain_tip.block_height);
+
+            let emily_status = match context
+                .emily()
+                .get_deposit(&utxo.txid.to_string(), &utxo.vout.to_string())
+                .await
+            {
+                Ok(Some(emily_deposit)) => emily_deposit.status.to_string(),
+                Ok(None) => "not found".to_string(),
+                Err(error) => format!("error: {error}"),
+            };
+
+            rows.push([
+                deposit.alias.clone(),
+                format!("{}:{}", utxo.txid, utxo.vout),
+                utxo.amount.to_sat().to_string(),
+                utxo.block_height.to_string(),
+                unlock_in.to_string(),
+                emily_status,
+            ]);
+        }
+    }
+
+    println!("chain tip: {chain_tip}");
+    print_table(
+        [
+            "ALIAS",
+            "OUTPOINT",
+            "AMOUNT (SATS)",
+            "HEIGHT",
+            "UNLOCK IN",
+            "EMILY STATUS",
+        ],
+        &rows,
+    );
+
+    Ok(())
+}
+
+/// Print the rows as a table, padding the columns to their widest cell
+fn print_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) {
+    let mut widths = header.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let print_row = |cells: Vec<&str>| {
+        let line = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    };
+
+    print_row(header.to_vec());
+    for row in rows {
+        print_row(row.iter().map(String::as_str).collect());
+    }
+}
+
+async fn scan_once(
+    context: Context,
+    monitored: Vec<MonitoredDeposit>,
+    mut submission: Submission,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let mut deposit_monitor = DepositMonitor::new(context.clone(), monitored);
+
+    let chain_tip = context.bitcoin_client().get_chain_tip()?;
+    tracing::debug!(%chain_tip, "processing pending deposits");
+
+    let summary =
+        fetch_and_create_deposits(&context, &mut deposit_monitor, &chain_tip, &mut submission)
+            .await;
+    context.notifier().flush().await;
+    let summary = summary?;
+
+    tracing::info!(
+        submitted = summary.submitted,
+        failed = summary.failed,
+        dry_run = submission.is_dry_run(),
+        "scan completed"
+    );
+
+    if summary.failed > 0 {
+        return Ok(ExitCode::from(EXIT_PARTIAL_FAILURE));
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn submit_deposit(
+    context: Context,
+    monitored: Vec<MonitoredDeposit>,
+    args: &SubmitDepositArgs,
+    mut submission: Submission,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut deposit_monitor = DepositMonitor::new(context.clone(), monitored);
+    deposit_monitor.refresh_limits().await;
+
+    let chain_tip = context.bitcoin_client().get_chain_tip()?;
+    let deposit = deposit_monitor.get_deposit_from_outpoint(
+        &OutPoint::new(args.txid, args.vout),
+        args.alias.as_deref(),
+        args.block_hash.as_ref(),
+        &chain_tip,
+    )?;
+
+    let summary = submit_deposits(&context, &deposit_monitor, vec![deposit], &mut submission).await;
+    context.notifier().flush().await;
+    let summary = summary?;
+    if summary.failed > 0 {
+        return Err(Box::new(Error::SubmissionFailed(summary.failed)));
     }
+
+    Ok(())
+}
+
+/// Check that the config matches the configured network
+fn validate_network(config: &Settings) -> Result<(), Box<dyn std::error::Error>> {
+    config.validate_network().inspect_err(|error| {
+        tracing::error!(%error, "invalid configuration for the network");
+    })?;
+
     Ok(())
 }
 
+/// Convert the configured deposits into monitored deposits
+fn monitored_deposits(config: &Settings) -> Result<Vec<MonitoredDeposit>, Error> {
+    config
+        .monitored_deposits()?
+        .iter()
+        .map(|(alias, deposit)| (alias, deposit).try_into())
+        .collect()
+}
+
+/// Connect to the nodes, refusing to run on a different Bitcoin network
+fn connect(config: &Settings) -> Result<Context, Box<dyn std::error::Error>> {
+    let context = Context::try_from(config)?;
+
+    context
+        .bitcoin_client()
+        .check_network(config.network().to_bitcoin_network())
+        .inspect_err(|error| {
+            tracing::error!(%error, "refusing to start on a different network");
+        })?;
+
+    Ok(context)
+}
+
+/// Run a command instead of the deposit monitor
+async fn run_command(
+    config: &Settings,
+    command: CliCommand,
+    submission: Submission,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    // The config check reports invalid deposits itself, so it must run before
+    // validating them.
+    if !matches!(command, CliCommand::CheckConfig) {
+        validate_network(config)?;
+    }
+
+    let success = |()| ExitCode::SUCCESS;
+    match command {
+        CliCommand::CheckConfig => check_config(config).await.map(success),
+        CliCommand::GetSignersXonlyKey => get_signers_xonly_key(config).await.map(success),
+        CliCommand::GetSignersInfo(args) => get_signers_info(config, &args).await.map(success),
+        CliCommand::Registry(args) => registry(config, &args).await.map(success),
+        CliCommand::GetDepositAddress(args) => {
+            get_deposit_address(config, &monitored_deposits(config)?, &args)
+                .await
+                .map(success)
+        }
+        CliCommand::CreateDepositTx(args) => {
+            create_deposit_tx(config, &monitored_deposits(config)?, &args)
+                .await
+                .map(success)
+        }
+        CliCommand::Status => status(config, &monitored_deposits(config)?)
+            .await
+            .map(success),
+        CliCommand::ScanOnce(_) => {
+            scan_once(connect(config)?, monitored_deposits(config)?, submission).await
+        }
+        CliCommand::SubmitDeposit(args) => submit_deposit(
+            connect(config)?,
+            monitored_deposits(config)?,
+            &args,
+            submission,
+        )
+        .await
+        .map(success),
+    }
+}
+
 #[tokio::main]
 #[tracing::instrument(name = "spox")]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> Result<ExitCode, Box<dyn std::error::Error>> {
     // Parse the command line arguments.
     let args = Args::parse();
 
@@ -162,29 +1043,239 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     spox::logging::setup_logging("info,spox=debug", pretty);
 
     // Load the configuration file and/or environment variables.
-    let config = Settings::new(args.config).inspect_err(|error| {
+    let config = Settings::new(args.config.as_ref()).inspect_err(|error| {
         tracing::error!(%error, "failed to construct the configuration");
     })?;
 
-    let monitored = config
-        .deposit
-        .iter()
-        .map(TryInto::try_into)
-        .collect::<Result<Vec<_>, Error>>()?;
+    let mut submission = Submission::new(&args)?;
 
-    match args.command {
-        Some(CliCommand::GetSignersXonlyKey) => return get_signers_xonly_key(&config).await,
-        Some(CliCommand::GetDepositAddress(args)) => {
-            return get_deposit_address(&monitored, &args).await;
-        }
-        None => (),
+    if let Some(command) = args.command {
+        return run_command(&config, command, submission).await;
     }
 
-    let context = Context::try_from(&config)?;
+    validate_network(&config)?;
+    let monitored = monitored_deposits(&config)?;
+    let context = connect(&config)?;
 
     let mut deposit_monitor = DepositMonitor::new(context.clone(), monitored);
 
-    runloop(context, &mut deposit_monitor, config.polling_interval).await;
+    if let Some(admin_api_config) = &config.admin_api {
+        let admin_api = AdminApi::new(
+            admin_api_config,
+            config.network(),
+            deposit_monitor.monitored().clone(),
+            deposit_monitor.activity().clone(),
+        )?;
 
-    Ok(())
+        let listener = admin_api.bind().await?;
+        tokio::spawn(async move {
+            if let Err(error) = admin_api.serve(listener).await {
+                tracing::error!(%error, "admin API stopped");
+            }
+        });
+    }
+
+    if submission.is_dry_run() {
+        tracing::warn!("dry run: deposits will not be submitted to emily");
+    }
+
+    runloop(
+        context,
+        &mut deposit_monitor,
+        config.polling_interval,
+        &mut submission,
+    )
+    .await;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::secp256k1::{PublicKey, SecretKey};
+    use clarity::vm::types::{PrincipalData, TupleData};
+    use clarity::vm::{ClarityName, Value};
+    use spox::testing;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(&["--dry-run", "scan-once"]; "global flag")]
+    #[test_case(&["scan-once", "--dry-run"]; "subcommand flag")]
+    #[tokio::test]
+    async fn scan_once_dry_run_does_not_call_emily(args: &[&str]) {
+        let mut emily = mockito::Server::new_async().await;
+        let create_deposit = emily
+            .mock("POST", mockito::Matcher::Any)
+            .expect(0)
+            .create_async()
+            .await;
+
+        let config_dir = tempfile::tempdir().unwrap();
+        let config_path = config_dir.path().join("config.toml");
+        let config = std::fs::read_to_string(testing::DEFAULT_CONFIG_PATH.unwrap())
+            .unwrap()
+            .replace(
+                "emily_endpoint = \"http://127.0.0.1:3031\"",
+                &format!("emily_endpoint = \"{}\"", emily.url()),
+            );
+        std::fs::write(&config_path, config).unwrap();
+        let context = Context::try_from(&Settings::new(Some(&config_path)).unwrap()).unwrap();
+        let deposit_monitor = DepositMonitor::new(context.clone(), Vec::new());
+
+        let args =
+            Args::try_parse_from(std::iter::once("spox").chain(args.iter().copied())).unwrap();
+        let mut submission = Submission::new(&args).unwrap();
+        assert!(submission.is_dry_run());
+
+        let deposit = CreateDepositRequestBody {
+            bitcoin_txid: "00".repeat(32),
+            ..Default::default()
+        };
+        let summary = submit_deposits(&context, &deposit_monitor, vec![deposit], &mut submission)
+            .await
+            .unwrap();
+
+        assert_eq!(summary.submitted, 1);
+        create_deposit.assert_async().await;
+    }
+
+    #[test_case(true; "agreeing aggregate keys")]
+    #[test_case(false; "disagreeing aggregate keys")]
+    #[tokio::test]
+    async fn get_signers_info_checks_aggregate_key(agree: bool) {
+        const DEPLOYER: &str = "SN3R84XZYA63QS28932XQF3G1J8R9PC3W76P9CSQS";
+
+        let keys = [[1; 32], [2; 32]].map(|secret| {
+            PublicKey::from_secret_key(SECP256K1, &SecretKey::from_slice(&secret).unwrap())
+        });
+        let aggregate_key = Value::buff_from(keys[0].serialize().to_vec()).unwrap();
+        let signer_data = TupleData::from_data(vec![
+            (ClarityName::from("current-aggregate-pubkey"), aggregate_key),
+            (
+                ClarityName::from("current-signer-set"),
+                Value::cons_list_unsanitized(
+                    keys.iter()
+                        .map(|key| Value::buff_from(key.serialize().to_vec()).unwrap())
+                        .collect(),
+                )
+                .unwrap(),
+            ),
+            (
+                ClarityName::from("current-signature-threshold"),
+                Value::UInt(2),
+            ),
+            (
+                ClarityName::from("current-signer-principal"),
+                Value::Principal(PrincipalData::parse(DEPLOYER).unwrap()),
+            ),
+        ])
+        .unwrap();
+        let checked_key = if agree { keys[0] } else { keys[1] };
+        let checked_key = Value::buff_from(checked_key.serialize().to_vec()).unwrap();
+
+        let mut stacks = mockito::Server::new_async().await;
+        let signer_data_mock = stacks
+            .mock(
+                "POST",
+                format!("/v2/contracts/call-read/{DEPLOYER}/sbtc-registry/get-current-signer-data")
+                    .as_str(),
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(format!(
+                r#"{{"okay":true,"result":"0x{}"}}"#,
+                Value::Tuple(signer_data).serialize_to_hex().unwrap()
+            ))
+            .expect(1)
+            .create_async()
+            .await;
+        let aggregate_key_mock = stacks
+            .mock(
+                "GET",
+                format!("/v2/data_var/{DEPLOYER}/sbtc-registry/current-aggregate-pubkey?proof=0")
+                    .as_str(),
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(format!(
+                r#"{{"data":"0x{}"}}"#,
+                checked_key.serialize_to_hex().unwrap()
+            ))
+            .expect(1)
+            .create_async()
+            .await;
+
+        let config_dir = tempfile::tempdir().unwrap();
+        let config_path = config_dir.path().join("config.toml");
+        let config = std::fs::read_to_string(testing::DEFAULT_CONFIG_PATH.unwrap())
+            .unwrap()
+            .replace(
+                "rpc_endpoint = \"http://127.0.0.1:20443\"",
+                &format!("rpc_endpoint = \"{}\"", stacks.url()),
+            );
+        std::fs::write(&config_path, config).unwrap();
+        let settings = Settings::new(Some(&config_path)).unwrap();
+
+        let info = fetch_signers_info(&settings).await;
+
+        if agree {
+            let info = info.unwrap();
+            let (xonly, _) = keys[0].x_only_public_key();
+            let peg_wallet = ScriptBuf::new_p2tr(SECP256K1, xonly, None);
+            assert_eq!(info.aggregate_key, Some(xonly.to_string()));
+            assert_eq!(
+                info.peg_wallet_script_pubkey,
+                Some(peg_wallet.to_hex_string())
+            );
+            assert_eq!(info.signature_threshold, 2);
+            assert_eq!(info.signer_set.len(), 2);
+        } else {
+            let error = info.unwrap_err();
+            assert!(matches!(
+                error.downcast_ref::<Error>(),
+                Some(Error::SignersAggregateKeyMismatch)
+            ));
+        }
+        signer_data_mock.assert_async().await;
+        aggregate_key_mock.assert_async().await;
+    }
+
+    #[test_case(&["--dry-run", "--dry-run-output", "out.jsonl", "scan-once"], true; "global flag")]
+    #[test_case(&["--dry-run-output", "out.jsonl", "scan-once", "--dry-run"], true; "subcommand flag")]
+    #[test_case(&["--dry-run-output", "out.jsonl", "submit-deposit", "--dry-run", "--txid", "0000000000000000000000000000000000000000000000000000000000000000", "--vout", "0"], true; "submit deposit flag")]
+    #[test_case(&["--dry-run-output", "out.jsonl", "scan-once"], false; "no dry run")]
+    fn dry_run_output_requires_a_dry_run(args: &[&str], is_valid: bool) {
+        let dir = tempfile::tempdir().unwrap();
+        let args = std::iter::once("spox")
+            .chain(args.iter().copied())
+            .map(|arg| {
+                if arg == "out.jsonl" {
+                    dir.path().join(arg).into_os_string()
+                } else {
+                    arg.into()
+                }
+            });
+        let args = Args::try_parse_from(args).unwrap();
+
+        match Submission::new(&args) {
+            Ok(submission) => {
+                assert!(is_valid);
+                assert!(matches!(submission, Submission::DryRun(Some(_))));
+                assert!(dir.path().join("out.jsonl").exists());
+            }
+            Err(error) => {
+                assert!(!is_valid);
+                assert!(matches!(error, Error::DryRunOutputWithoutDryRun));
+            }
+        }
+    }
+
+    #[test_case("alice", "alice.png"; "plain alias")]
+    #[test_case("users-SP000.contract", "users-SP000_contract.png"; "contract principal")]
+    #[test_case("../etc/passwd", "___etc_passwd.png"; "path traversal")]
+    #[test_case("..", "__.png"; "parent directory")]
+    fn qr_file_name_stays_in_directory(alias: &str, expected: &str) {
+        assert_eq!(qr_file_name(alias, "png"), expected);
+    }
 }
@@ -0,0 +1,380 @@
+//! Module to notify webhooks about the deposits lifecycle
+//!
+//! Events are POSTed as JSON objects, tagged by their `event` name and
+//! timestamped. If the webhook has a secret, the body is signed with
+//! HMAC-SHA256 and the signature sent as `X-Spox-Signature: sha256=<hex>`.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bitcoin::hashes::hmac::{Hmac, HmacEngine};
+use bitcoin::hashes::{Hash as _, HashEngine as _, sha256};
+use bitcoin::{BlockHash, Txid};
+use serde::Serialize;
+use tokio::task::JoinSet;
+use url::Url;
+
+use crate::config::{NotificationsConfig, ProxyConfig, Secret, TlsConfig};
+use crate::deposit_monitor::DepositRecord;
+use crate::error::Error;
+use crate::{proxy, tls};
+
+/// The names of all the events, as used in the webhooks `events` config
+pub const EVENT_KINDS: [&str; 6] = [
+    "deposit_detected",
+    "deposit_submitted",
+    "submission_failed",
+    "deposit_nearing_expiry",
+    "deposit_expired",
+    "chain_tip_stalled",
+];
+
+/// The header carrying the HMAC-SHA256 signature of the body
+pub const SIGNATURE_HEADER: &str = "X-Spox-Signature";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The delay before the first retry, doubled at each retry
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// A deposit lifecycle event
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// A new UTXO was found at a monitored deposit address
+    DepositDetected(DepositRecord),
+    /// The deposit was submitted to Emily
+    DepositSubmitted {
+        /// The monitored deposit alias, if known
+        alias: Option<String>,
+        /// The deposit transaction id
+        txid: Txid,
+        /// The deposit output index
+        vout: u32,
+    },
+    /// The deposit could not be submitted to Emily
+    SubmissionFailed {
+        /// The monitored deposit alias, if known
+        alias: Option<String>,
+        /// The deposit transaction id
+        txid: Txid,
+        /// The deposit output index
+        vout: u32,
+        /// The submission error
+        error: String,
+    },
+    /// The deposit is still pending close to its reclaim unlock height
+    DepositNearingExpiry {
+        /// The deposit UTXO
+        #[serde(flatten)]
+        deposit: DepositRecord,
+        /// The blocks left before the reclaim unlock height
+        blocks_left: u64,
+    },
+    /// The deposit reached its reclaim unlock height, so it will not be
+    /// processed by the signers
+    DepositExpired(DepositRecord),
+    /// The chain tip did not change for a while
+    ChainTipStalled {
+        /// The stalled chain tip height
+        block_height: u64,
+        /// The stalled chain tip hash
+        block_hash: BlockHash,
+        /// For how long the chain tip did not change, in seconds
+        stalled_for: u64,
+    },
+}
+
+impl Event {
+    /// The event name, one of [`EVENT_KINDS`]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::DepositDetected(_) => "deposit_detected",
+            Event::DepositSubmitted { .. } => "deposit_submitted",
+            Event::SubmissionFailed { .. } => "submission_failed",
+            Event::DepositNearingExpiry { .. } => "deposit_nearing_expiry",
+            Event::DepositExpired(_) => "deposit_expired",
+            Event::ChainTipStalled { .. } => "chain_tip_stalled",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    timestamp: u64,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+#[derive(Debug)]
+struct Webhook {
+    name: String,
+    url: Url,
+    secret: Option<Secret>,
+    events: Vec<String>,
+    max_retries: u32,
+}
+
+impl Webhook {
+    fn wants(&self, event: &Event) -> bool {
+        self.events.is_empty() || self.events.iter().any(|kind| kind == event.kind())
+    }
+}
+
+/// Sends the deposit lifecycle events to the configured webhooks
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    webhooks: Arc<Vec<Webhook>>,
+    client: reqwest::Client,
+    expiry_warning_blocks: u64,
+    chain_tip_stall_timeout: Duration,
+    /// The deliveries running in the background
+    deliveries: Arc<Mutex<JoinSet<()>>>,
+}
+
+impl Notifier {
+    /// Create a new notifier for the configured webhooks, connecting through
+    /// the same TLS and proxy setup as the Emily and Stacks clients
+    pub fn new(
+        config: &NotificationsConfig,
+        tls: &TlsConfig,
+        proxy: Option<&ProxyConfig>,
+    ) -> Result<Self, Error> {
+        let mut webhooks = config
+            .webhook
+            .iter()
+            .map(|(name, webhook)| Webhook {
+                name: name.clone(),
+                url: webhook.url.clone(),
+                secret: webhook.secret.clone(),
+                events: webhook.events.clone(),
+                max_retries: webhook.max_retries,
+            })
+            .collect::<Vec<_>>();
+        webhooks.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let builder = reqwest::Client::builder().timeout(REQUEST_TIMEOUT);
+        let builder = tls::configure_reqwest(builder, tls)?;
+        let client = proxy::configure_reqwest(builder, proxy)?.build()?;
+
+        Ok(Self {
+            webhooks: Arc::new(webhooks),
+            client,
+            expiry_warning_blocks: config.expiry_warning_blocks,
+            chain_tip_stall_timeout: config.chain_tip_stall_timeout,
+            deliveries: Arc::default(),
+        })
+    }
+
+    /// How many blocks before its reclaim unlock a pending deposit is
+    /// reported as nearing expiry
+    pub fn expiry_warning_blocks(&self) -> u64 {
+        self.expiry_warning_blocks
+    }
+
+    /// How long the chain tip can stay the same before being reported as
+    /// stalled
+    pub fn chain_tip_stall_timeout(&self) -> Duration {
+        self.chain_tip_stall_timeout
+    }
+
+    /// Send the event to the webhooks in the background; see [`Self::flush`]
+    /// to wait for the delivery.
+    pub fn notify(&self, event: Event) {
+        if !self.webhooks.iter().any(|webhook| webhook.wants(&event)) {
+            return;
+        }
+
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            tracing::warn!(event = event.kind(), "no async runtime; dropping event");
+            return;
+        };
+
+        let notifier = self.clone();
+        let mut deliveries = self.deliveries.lock().expect("deliveries lock poisoned");
+        while deliveries.try_join_next().is_some() {}
+        deliveries.spawn_on(async move { notifier.send(&event).await }, &runtime);
+    }
+
+    /// Wait for the events sent in the background to be delivered, or to
+    /// exhaust their retries. One-shot commands must call it before exiting,
+    /// which would otherwise drop the pending deliveries.
+    pub async fn flush(&self) {
+        let mut deliveries =
+            std::mem::take(&mut *self.deliveries.lock().expect("deliveries lock poisoned"));
+        while deliveries.join_next().await.is_some() {}
+    }
+
+    /// Send the event to the webhooks, retrying failed deliveries
+    pub async fn send(&self, event: &Event) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let body = match serde_json::to_vec(&Payload { timestamp, event }) {
+            Ok(body) => body,
+            Err(error) => {
+                tracing::error!(%error, event = event.kind(), "cannot serialize event");
+                return;
+            }
+        };
+
+        for webhook in self.webhooks.iter().filter(|webhook| webhook.wants(event)) {
+            if let Err(error) = self.deliver(webhook, &body).await {
+                tracing::warn!(
+                    %error,
+                    webhook = %webhook.name,
+                    event = event.kind(),
+                    "cannot deliver event to webhook"
+                );
+            }
+        }
+    }
+
+    async fn deliver(&self, webhook: &Webhook, body: &[u8]) -> Result<(), Error> {
+        let mut attempt = 0;
+        loop {
+            let mut request = self
+                .client
+                .post(webhook.url.clone())
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.to_vec());
+            if let Some(secret) = &webhook.secret {
+                request = request.header(SIGNATURE_HEADER, sign(secret, body));
+            }
+
+            match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => return Ok(()),
+                Err(error) if attempt >= webhook.max_retries => return Err(error.into()),
+                Err(error) => {
+                    tracing::debug!(
+                        %error,
+                        webhook = %webhook.name,
+                        attempt,
+                        "webhook delivery failed; retrying"
+                    );
+                    let delay = RETRY_BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt));
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Sign the body with HMAC-SHA256, formatted as `sha256=<hex>`
+pub fn sign(secret: &Secret, body: &[u8]) -> String {
+    let mut engine = HmacEngine::<sha256::Hash>::new(secret.expose().as_bytes());
+    engine.input(body);
+    let hmac = Hmac::<sha256::Hash>::from_engine(engine);
+    format!("sha256={hmac}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use bitcoin::hashes::Hash as _;
+
+    use super::*;
+    use crate::config::WebhookConfig;
+
+    fn notifier(webhooks: HashMap<String, WebhookConfig>) -> Notifier {
+        let config = NotificationsConfig {
+            webhook: webhooks,
+            expiry_warning_blocks: 6,
+            chain_tip_stall_timeout: Duration::from_secs(3600),
+        };
+        Notifier::new(&config, &TlsConfig::default(), None).unwrap()
+    }
+
+    fn webhook(server: &mockito::Server, events: &[&str]) -> WebhookConfig {
+        WebhookConfig {
+            url: format!("{}/hook", server.url()).parse().unwrap(),
+            secret: Some(Secret::from("secret".to_string())),
+            events: events.iter().map(ToString::to_string).collect(),
+            max_retries: 1,
+        }
+    }
+
+    fn submitted_event() -> Event {
+        Event::DepositSubmitted {
+            alias: Some("alice".to_string()),
+            txid: Txid::all_zeros(),
+            vout: 1,
+        }
+    }
+
+    #[test]
+    fn event_kinds_match() {
+        let event = submitted_event();
+        assert!(EVENT_KINDS.contains(&event.kind()));
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], event.kind());
+        assert_eq!(json["alias"], "alice");
+    }
+
+    #[tokio::test]
+    async fn send_signs_and_retries() {
+        let mut server = mockito::Server::new_async().await;
+        let failure = server
+            .mock("POST", "/hook")
+            .with_status(500)
+            .expect(1)
+            .create_async()
+            .await;
+        let success = server
+            .mock("POST", "/hook")
+            .match_request(|request| {
+                let expected = sign(&Secret::from("secret".to_string()), request.body().unwrap());
+                let signature = request.header(SIGNATURE_HEADER);
+                signature.first().and_then(|value| value.to_str().ok()) == Some(expected.as_str())
+            })
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "event": "deposit_submitted",
+                "vout": 1,
+            })))
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let notifier = notifier(HashMap::from([("ops".to_string(), webhook(&server, &[]))]));
+        notifier.send(&submitted_event()).await;
+
+        failure.assert_async().await;
+        success.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn send_filters_events() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("POST", "/hook").expect(0).create_async().await;
+
+        let notifier = notifier(HashMap::from([(
+            "ops".to_string(),
+            webhook(&server, &["deposit_expired"]),
+        )]));
+        notifier.send(&submitted_event()).await;
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn flush_waits_for_deliveries() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/hook")
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let notifier = notifier(HashMap::from([("ops".to_string(), webhook(&server, &[]))]));
+        notifier.notify(submitted_event());
+        notifier.flush().await;
+
+        mock.assert_async().await;
+    }
+}
@@ -0,0 +1,22 @@
+//! Proxy setup for the outbound HTTP connections
+
+use reqwest::{NoProxy, Proxy};
+
+use crate::config::ProxyConfig;
+use crate::error::Error;
+
+/// Route the reqwest client requests through the proxy, if any, except for
+/// the `no_proxy` hosts
+pub fn configure_reqwest(
+    builder: reqwest::ClientBuilder,
+    config: Option<&ProxyConfig>,
+) -> Result<reqwest::ClientBuilder, Error> {
+    let Some(config) = config else {
+        return Ok(builder);
+    };
+
+    let proxy =
+        Proxy::all(config.url.clone())?.no_proxy(NoProxy::from_string(&config.no_proxy.join(",")));
+
+    Ok(builder.proxy(proxy))
+}
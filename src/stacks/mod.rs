@@ -1,3 +1,23 @@
 //! Contains functionality for interacting with the Stacks blockchain
 
+use clarity::types::chainstate::StacksAddress;
+use clarity::vm::types::{PrincipalData, StandardPrincipalData};
+
 pub mod node;
+
+/// The c32 versions of mainnet addresses, single-sig (`SP`) and multi-sig
+/// (`SM`).
+const MAINNET_ADDRESS_VERSIONS: [u8; 2] = [22, 20];
+
+/// Whether the principal (or the issuer of a contract principal) is a
+/// mainnet address.
+pub fn is_mainnet_principal(principal: &PrincipalData) -> bool {
+    MAINNET_ADDRESS_VERSIONS.contains(&principal.version())
+}
+
+/// Whether the address is a mainnet address.
+pub fn is_mainnet_address(address: &StacksAddress) -> bool {
+    is_mainnet_principal(&PrincipalData::from(StandardPrincipalData::from(
+        address.clone(),
+    )))
+}
@@ -1,19 +1,25 @@
 //! A module with structs that interact with the Stacks API.
 
 use std::borrow::Cow;
+use std::num::NonZero;
 use std::time::Duration;
 
-use bitcoin::{PublicKey, XOnlyPublicKey};
-use clarity::types::chainstate::StacksAddress;
-use clarity::vm::types::{BuffData, SequenceData};
+use bitcoin::hashes::Hash as _;
+use bitcoin::{OutPoint, PublicKey, XOnlyPublicKey};
+use clarity::types::chainstate::{BlockHeaderHash, ConsensusHash, StacksAddress, StacksBlockId};
+use clarity::vm::types::{BuffData, PrincipalData, SequenceData, TupleData};
 use clarity::vm::{ClarityName, ContractName, Value};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Deserializer};
 use url::Url;
 
-use crate::config::Settings;
+use crate::config::error::SpoxConfigError;
+use crate::config::{ProxyConfig, Settings, StacksConfig, TlsConfig};
 use crate::error::Error;
+use crate::{proxy, tls};
 
-const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// The delay before the first retry, doubled at each retry
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
 
 /// The response from a GET /v2/data_var/<contract-principal>/<contract-name>/<var-name> request.
 #[derive(Debug, Deserialize)]
@@ -21,40 +27,97 @@ pub struct DataVarResponse {
     /// The value of the data variable.
     #[serde(deserialize_with = "clarity_value_deserializer")]
     pub data: Value,
+    /// The hex-encoded MARF proof of the value, if requested.
+    #[serde(default)]
+    pub proof: Option<String>,
+}
+
+/// The response from a POST /v2/contracts/call-read/<contract-principal>/<contract-name>/<function-name> request.
+#[derive(Debug, Deserialize)]
+pub struct CallReadOnlyResponse {
+    /// Whether the call succeeded.
+    pub okay: bool,
+    /// The hex-encoded result of the call, if it succeeded.
+    #[serde(default)]
+    pub result: Option<String>,
+    /// Why the call failed, if it did.
+    #[serde(default)]
+    pub cause: Option<String>,
+}
+
+/// The current signers data in the `sbtc-registry` contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignerData {
+    /// The signers aggregate key, `None` before the first key rotation.
+    pub aggregate_key: Option<XOnlyPublicKey>,
+    /// The public keys of the signers.
+    pub signer_set: Vec<PublicKey>,
+    /// How many signatures are required.
+    pub signature_threshold: u64,
+    /// The principal of the signers multi-sig.
+    pub signer_principal: PrincipalData,
+}
+
+/// The subset of the GET /v2/info response identifying the chain tip.
+#[derive(Debug, Deserialize)]
+pub struct InfoResponse {
+    /// The block hash of the chain tip.
+    pub stacks_tip: String,
+    /// The consensus hash of the chain tip.
+    pub stacks_tip_consensus_hash: String,
 }
 
 /// A client for interacting with Stacks nodes and the Stacks API
 #[derive(Debug, Clone)]
 pub struct StacksClient {
-    /// The base url for the Stacks node's RPC API.
-    pub endpoint: Url,
+    /// The base urls for the Stacks nodes' RPC API, tried in order.
+    pub endpoints: Vec<Url>,
     /// The client used to make the request.
     pub client: reqwest::Client,
     /// The address of the deployer of the sBTC smart contracts.
     pub deployer: StacksAddress,
+    /// How many times a failed request to an endpoint is retried.
+    pub max_retries: u32,
+    /// How many endpoints must agree on the signers aggregate key.
+    pub quorum: Option<NonZero<usize>>,
+    /// Whether the endpoints must agree on the proof of the signers
+    /// aggregate key too.
+    pub compare_proofs: bool,
 }
 
 impl StacksClient {
     /// Create a new instance of the Stacks client using the given
-    /// StacksSettings.
-    pub fn new(url: Url, deployer: StacksAddress) -> Result<Self, Error> {
-        let client = reqwest::Client::builder()
-            .timeout(REQUEST_TIMEOUT)
-            .build()?;
+    /// StacksSettings. At least one endpoint is required, and comparing
+    /// proofs requires a quorum of at least 2 endpoints to compare.
+    pub fn new(
+        config: &StacksConfig,
+        tls: &TlsConfig,
+        proxy: Option<&ProxyConfig>,
+    ) -> Result<Self, Error> {
+        if config.rpc_endpoint.is_empty() {
+            return Err(SpoxConfigError::NoStacksEndpoint.into());
+        }
+        if config.compare_proofs && config.quorum.is_none_or(|quorum| quorum.get() < 2) {
+            return Err(SpoxConfigError::StacksProofsWithoutQuorum.into());
+        }
+
+        let builder = reqwest::Client::builder().timeout(config.timeout);
+        let builder = tls::configure_reqwest(builder, tls)?;
+        let client = proxy::configure_reqwest(builder, proxy)?.build()?;
 
         Ok(Self {
-            endpoint: url,
+            endpoints: config.rpc_endpoint.clone(),
             client,
-            deployer,
+            deployer: config.deployer.clone(),
+            max_retries: config.max_retries,
+            quorum: config.quorum,
+            compare_proofs: config.compare_proofs,
         })
     }
 
     /// Retrieve the latest value of a data variable from the specified contract.
     ///
-    /// This is done by making a
-    /// `GET /v2/data_var/<contract-principal>/<contract-name>/<var-name>`
-    /// request. In the request we specify that the proof should not be included
-    /// in the response.
+    /// The endpoints are tried in order, until one of them answers.
     #[tracing::instrument(skip_all)]
     pub async fn get_data_var(
         &self,
@@ -62,48 +125,446 @@ impl StacksClient {
         contract_name: &ContractName,
         var_name: &ClarityName,
     ) -> Result<Value, Error> {
-        let path = format!("/v2/data_var/{contract_principal}/{contract_name}/{var_name}?proof=0",);
+        self.failover("cannot fetch contract data variable", async |endpoint| {
+            self.get_data_var_from(endpoint, contract_principal, contract_name, var_name, None)
+                .await
+                .map(|response| response.data)
+        })
+        .await
+    }
+
+    /// Retrieve the value of a map entry from the specified contract, `None`
+    /// if the map has no such key.
+    ///
+    /// This is done by making a
+    /// `POST /v2/map_entry/<contract-principal>/<contract-name>/<map-name>`
+    /// request, with the hex-encoded key as body. In the request we specify
+    /// that the proof should not be included in the response.
+    #[tracing::instrument(skip_all)]
+    pub async fn get_map_entry(
+        &self,
+        contract_principal: &StacksAddress,
+        contract_name: &ContractName,
+        map_name: &ClarityName,
+        key: &Value,
+    ) -> Result<Option<Value>, Error> {
+        let path = format!("/v2/map_entry/{contract_principal}/{contract_name}/{map_name}?proof=0");
+        let body = serde_json::Value::String(format!("0x{}", serialize_hex(key)?));
+
+        tracing::debug!(
+            %contract_principal,
+            %contract_name,
+            %map_name,
+            "fetching contract map entry"
+        );
+
+        let value = self
+            .failover("cannot fetch contract map entry", async |endpoint| {
+                let url = join_path(endpoint, &path)?;
+                self.send_json::<DataVarResponse>(reqwest::Method::POST, url, Some(&body))
+                    .await
+                    .map(|response| response.data)
+            })
+            .await?;
+
+        match value {
+            Value::Optional(entry) => Ok(entry.data.map(|data| *data)),
+            _ => Err(Error::InvalidStacksResponse(
+                "expected an optional but got something else",
+            )),
+        }
+    }
+
+    /// Call a read-only function of the specified contract, with the
+    /// deployer as sender.
+    ///
+    /// This is done by making a
+    /// `POST /v2/contracts/call-read/<contract-principal>/<contract-name>/<function-name>`
+    /// request, with the hex-encoded arguments.
+    #[tracing::instrument(skip_all)]
+    pub async fn call_read_only(
+        &self,
+        contract_principal: &StacksAddress,
+        contract_name: &ContractName,
+        function_name: &ClarityName,
+        arguments: &[Value],
+    ) -> Result<Value, Error> {
+        let path =
+            format!("/v2/contracts/call-read/{contract_principal}/{contract_name}/{function_name}");
+        let arguments = arguments
+            .iter()
+            .map(|argument| serialize_hex(argument).map(|hex| format!("0x{hex}")))
+            .collect::<Result<Vec<_>, _>>()?;
+        let body = serde_json::json!({
+            "sender": self.deployer.to_string(),
+            "arguments": arguments,
+        });
+
+        tracing::debug!(
+            %contract_principal,
+            %contract_name,
+            %function_name,
+            "calling contract read-only function"
+        );
+
+        let response = self
+            .failover(
+                "cannot call contract read-only function",
+                async |endpoint| {
+                    let url = join_path(endpoint, &path)?;
+                    self.send_json::<CallReadOnlyResponse>(reqwest::Method::POST, url, Some(&body))
+                        .await
+                },
+            )
+            .await?;
+
+        match response {
+            CallReadOnlyResponse {
+                okay: true,
+                result: Some(result),
+                ..
+            } => Value::try_deserialize_hex_untyped(&result)
+                .map_err(|error| Error::Clarity(error.to_string())),
+            CallReadOnlyResponse { cause, .. } => Err(Error::ReadOnlyCallFailed(
+                function_name.to_string(),
+                cause.unwrap_or_default(),
+            )),
+        }
+    }
 
-        let url = self
-            .endpoint
-            .join(&path)
-            .map_err(|err| Error::PathJoin(err, self.endpoint.clone(), Cow::Owned(path)))?;
+    /// Run the request against the endpoints in order, until one of them
+    /// answers.
+    async fn failover<T>(
+        &self,
+        message: &str,
+        request: impl AsyncFn(&Url) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let mut last_error = None;
+        for endpoint in &self.endpoints {
+            match request(endpoint).await {
+                Ok(value) => return Ok(value),
+                Err(error) => {
+                    tracing::warn!(%error, %endpoint, "{message}");
+                    last_error = Some(error);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or(Error::Config(SpoxConfigError::NoStacksEndpoint)))
+    }
+
+    /// Retrieve the value of a data variable from the specified contract,
+    /// using a single endpoint.
+    ///
+    /// This is done by making a
+    /// `GET /v2/data_var/<contract-principal>/<contract-name>/<var-name>`
+    /// request. Without a tip, we read the latest value and specify that the
+    /// proof should not be included in the response; with a tip, we read the
+    /// value at that block and include the proof.
+    async fn get_data_var_from(
+        &self,
+        endpoint: &Url,
+        contract_principal: &StacksAddress,
+        contract_name: &ContractName,
+        var_name: &ClarityName,
+        tip: Option<&StacksBlockId>,
+    ) -> Result<DataVarResponse, Error> {
+        let query = match tip {
+            Some(tip) => format!("proof=1&tip={}", tip.to_hex()),
+            None => "proof=0".to_string(),
+        };
+        let path = format!("/v2/data_var/{contract_principal}/{contract_name}/{var_name}?{query}");
+        let url = join_path(endpoint, &path)?;
 
         tracing::debug!(
+            %endpoint,
             %contract_principal,
             %contract_name,
             %var_name,
             "fetching contract data variable"
         );
 
-        let response = self
-            .client
-            .get(url)
-            .send()
-            .await
-            .map_err(Error::StacksNodeRequest)?;
+        self.get_json::<DataVarResponse>(url).await
+    }
+
+    /// Retrieve the index block hash of the chain tip from the first endpoint
+    /// answering.
+    pub async fn get_tip(&self) -> Result<StacksBlockId, Error> {
+        let info = self
+            .failover("cannot fetch the chain tip", async |endpoint| {
+                self.get_json::<InfoResponse>(join_path(endpoint, "/v2/info")?)
+                    .await
+            })
+            .await?;
+
+        let consensus_hash = ConsensusHash::from_hex(&info.stacks_tip_consensus_hash)
+            .map_err(|_| Error::InvalidStacksResponse("invalid tip consensus hash"))?;
+        let block_hash = BlockHeaderHash::from_hex(&info.stacks_tip)
+            .map_err(|_| Error::InvalidStacksResponse("invalid tip block hash"))?;
+        Ok(StacksBlockId::new(&consensus_hash, &block_hash))
+    }
+
+    /// Make a GET request, retrying with exponential backoff on connection
+    /// errors and server errors.
+    async fn get_json<T: DeserializeOwned>(&self, url: Url) -> Result<T, Error> {
+        self.send_json(reqwest::Method::GET, url, None).await
+    }
+
+    /// Make a request with an optional JSON body, retrying with exponential
+    /// backoff on connection errors and server errors.
+    async fn send_json<T: DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        url: Url,
+        body: Option<&serde_json::Value>,
+    ) -> Result<T, Error> {
+        let mut attempt = 0;
+        loop {
+            match self.try_send_json(method.clone(), url.clone(), body).await {
+                Ok(value) => return Ok(value),
+                Err(error) if attempt >= self.max_retries || !is_retryable(&error) => {
+                    return Err(error);
+                }
+                Err(error) => {
+                    tracing::debug!(%error, %url, attempt, "stacks request failed; retrying");
+                    let delay = RETRY_BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt));
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    async fn try_send_json<T: DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        url: Url,
+        body: Option<&serde_json::Value>,
+    ) -> Result<T, Error> {
+        let mut request = self.client.request(method, url);
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        let response = request.send().await.map_err(Error::StacksNodeRequest)?;
 
         response
             .error_for_status()
             .map_err(Error::StacksNodeResponse)?
-            .json::<DataVarResponse>()
+            .json::<T>()
             .await
             .map_err(Error::UnexpectedStacksResponse)
-            .map(|x| x.data)
     }
 
     /// Retrieve the current signers' aggregate key from the `sbtc-registry`
     /// contract.
+    ///
+    /// With a quorum, the key is fetched from every endpoint until enough of
+    /// them agree on it. When comparing proofs, the key is read with its MARF
+    /// proof at the chain tip of the first endpoint answering, and the
+    /// endpoints must agree on the proof too. The proof is not verified
+    /// locally: a node can only forge it by disagreeing with the other
+    /// endpoints.
     pub async fn get_current_signers_aggregate_key(&self) -> Result<Option<XOnlyPublicKey>, Error> {
+        let contract_name = ContractName::from("sbtc-registry");
+        let var_name = ClarityName::from("current-aggregate-pubkey");
+
+        let tip = if self.compare_proofs {
+            Some(self.get_tip().await?)
+        } else {
+            None
+        };
+        let quorum = self.quorum.map_or(1, NonZero::get);
+
+        if quorum == 1 && tip.is_none() {
+            let value = self
+                .get_data_var(&self.deployer, &contract_name, &var_name)
+                .await?;
+            return extract_aggregate_key(value);
+        }
+
+        let mut votes = Vec::<((Option<XOnlyPublicKey>, Option<String>), usize)>::new();
+        for endpoint in &self.endpoints {
+            let key = match self
+                .get_data_var_from(
+                    endpoint,
+                    &self.deployer,
+                    &contract_name,
+                    &var_name,
+                    tip.as_ref(),
+                )
+                .await
+                .and_then(|response| {
+                    if tip.is_some() && response.proof.as_deref().is_none_or(str::is_empty) {
+                        return Err(Error::InvalidStacksResponse("missing proof"));
+                    }
+                    Ok((extract_aggregate_key(response.data)?, response.proof))
+                }) {
+                Ok(key) => key,
+                Err(error) => {
+                    tracing::warn!(%error, %endpoint, "cannot fetch the signers aggregate key");
+                    continue;
+                }
+            };
+
+            let count = match votes.iter_mut().find(|(voted, _)| *voted == key) {
+                Some((_, count)) => {
+                    *count += 1;
+                    *count
+                }
+                None => {
+                    votes.push((key.clone(), 1));
+                    1
+                }
+            };
+            if count >= quorum {
+                return Ok(key.0);
+            }
+        }
+
+        if votes.len() > 1 {
+            tracing::warn!(
+                distinct_keys = votes.len(),
+                "stacks endpoints disagree on the signers aggregate key"
+            );
+        }
+        let agreeing = votes.iter().map(|(_, count)| *count).max().unwrap_or(0);
+        Err(Error::StacksQuorumNotReached(quorum, agreeing))
+    }
+}
+
+/// Queries of the `sbtc-registry` contract.
+impl StacksClient {
+    /// Retrieve the current signers data: the aggregate key, the signer set
+    /// public keys, the signatures required and the signers principal.
+    pub async fn get_current_signer_data(&self) -> Result<SignerData, Error> {
         let value = self
-            .get_data_var(
+            .call_read_only(
                 &self.deployer,
                 &ContractName::from("sbtc-registry"),
-                &ClarityName::from("current-aggregate-pubkey"),
+                &ClarityName::from("get-current-signer-data"),
+                &[],
             )
             .await?;
 
-        extract_aggregate_key(value)
+        let aggregate_key =
+            extract_aggregate_key(tuple_field(&value, "current-aggregate-pubkey")?.clone())?;
+        let signer_set = match tuple_field(&value, "current-signer-set")? {
+            Value::Sequence(SequenceData::List(list)) => list
+                .data
+                .iter()
+                .map(|key| match key {
+                    Value::Sequence(SequenceData::Buffer(buffer)) => {
+                        PublicKey::from_slice(&buffer.data).map_err(Error::InvalidPublicKey)
+                    }
+                    _ => Err(Error::InvalidStacksResponse(
+                        "expected a buffer but got something else",
+                    )),
+                })
+                .collect::<Result<_, _>>()?,
+            _ => {
+                return Err(Error::InvalidStacksResponse(
+                    "expected a list but got something else",
+                ));
+            }
+        };
+        let signature_threshold = match tuple_field(&value, "current-signature-threshold")? {
+            Value::UInt(threshold) => u64::try_from(*threshold)
+                .map_err(|_| Error::InvalidStacksResponse("signature threshold overflow"))?,
+            _ => {
+                return Err(Error::InvalidStacksResponse(
+                    "expected a uint but got something else",
+                ));
+            }
+        };
+        let signer_principal = match tuple_field(&value, "current-signer-principal")? {
+            Value::Principal(principal) => principal.clone(),
+            _ => {
+                return Err(Error::InvalidStacksResponse(
+                    "expected a principal but got something else",
+                ));
+            }
+        };
+
+        Ok(SignerData {
+            aggregate_key,
+            signer_set,
+            signature_threshold,
+            signer_principal,
+        })
+    }
+
+    /// Retrieve the `deposit-status` entry of the deposit in the
+    /// `sbtc-registry` contract: `Some(true)` once the signers completed it,
+    /// i.e. minted its sBTC, and `None` if they did not process it yet. The
+    /// contract only writes `true`, so `Some(false)`, an entry not marked
+    /// as completed, is not a failure and only means the deposit is not
+    /// minted.
+    pub async fn get_deposit_status(&self, outpoint: &OutPoint) -> Result<Option<bool>, Error> {
+        let txid = Value::buff_from(outpoint.txid.to_byte_array().to_vec())
+            .map_err(|error| Error::Clarity(error.to_string()))?;
+        let key = TupleData::from_data(vec![
+            (ClarityName::from("txid"), txid),
+            (
+                ClarityName::from("vout-index"),
+                Value::UInt(outpoint.vout.into()),
+            ),
+        ])
+        .map_err(|error| Error::Clarity(error.to_string()))?;
+
+        let status = self
+            .get_map_entry(
+                &self.deployer,
+                &ContractName::from("sbtc-registry"),
+                &ClarityName::from("deposit-status"),
+                &Value::Tuple(key),
+            )
+            .await?;
+
+        match status {
+            None => Ok(None),
+            Some(Value::Bool(status)) => Ok(Some(status)),
+            Some(_) => Err(Error::InvalidStacksResponse(
+                "expected a bool but got something else",
+            )),
+        }
+    }
+}
+
+/// Join the path to the endpoint base url.
+fn join_path(endpoint: &Url, path: &str) -> Result<Url, Error> {
+    endpoint
+        .join(path)
+        .map_err(|err| Error::PathJoin(err, endpoint.clone(), Cow::Owned(path.to_string())))
+}
+
+/// Serialize the Clarity value into its consensus hex encoding.
+fn serialize_hex(value: &Value) -> Result<String, Error> {
+    value
+        .serialize_to_hex()
+        .map_err(|error| Error::Clarity(error.to_string()))
+}
+
+/// Get a field of a Clarity tuple.
+fn tuple_field<'a>(value: &'a Value, name: &str) -> Result<&'a Value, Error> {
+    match value {
+        Value::Tuple(tuple) => tuple
+            .get(name)
+            .map_err(|_| Error::InvalidStacksResponse("missing tuple field")),
+        _ => Err(Error::InvalidStacksResponse(
+            "expected a tuple but got something else",
+        )),
+    }
+}
+
+/// Whether the request may succeed if retried: connection errors, timeouts,
+/// rate limiting and server errors.
+fn is_retryable(error: &Error) -> bool {
+    match error {
+        Error::StacksNodeRequest(_) => true,
+        Error::StacksNodeResponse(error) => error.status().is_none_or(|status| {
+            status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        }),
+        _ => false,
     }
 }
 
@@ -113,10 +574,10 @@ impl TryFrom<&Settings> for StacksClient {
     fn try_from(value: &Settings) -> Result<Self, Self::Error> {
         let stacks_config = value
             .stacks
-            .clone()
+            .as_ref()
             .ok_or_else(|| Error::MissingStacksConfig)?;
 
-        StacksClient::new(stacks_config.rpc_endpoint, stacks_config.deployer)
+        StacksClient::new(stacks_config, &value.tls, value.proxy.as_ref())
     }
 }
 
@@ -165,6 +626,34 @@ mod tests {
 
     use super::*;
 
+    const DEPLOYER: &str = "ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM";
+    const AGGREGATE_KEY_PATH: &str = "/v2/data_var/ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM/sbtc-registry/current-aggregate-pubkey?proof=0";
+
+    /// Helper method for building a Stacks config for the endpoints.
+    fn stacks_config(endpoints: &[&mockito::Server]) -> StacksConfig {
+        StacksConfig {
+            rpc_endpoint: endpoints
+                .iter()
+                .map(|server| server.url().parse().unwrap())
+                .collect(),
+            deployer: StacksAddress::from_string(DEPLOYER).unwrap(),
+            timeout: Duration::from_secs(5),
+            max_retries: 0,
+            quorum: None,
+            compare_proofs: false,
+        }
+    }
+
+    /// Helper method for building a data var response with the aggregate key.
+    fn aggregate_key_response(aggregate_key: &PublicKey) -> String {
+        let data = aggregate_key.inner.serialize().to_vec();
+        let value = Value::Sequence(SequenceData::Buffer(BuffData { data }));
+        format!(
+            r#"{{"data":"0x{}"}}"#,
+            Value::serialize_to_hex(&value).unwrap()
+        )
+    }
+
     /// Helper method for generating a list of public keys.
     fn generate_pubkeys(count: u16) -> Vec<PublicKey> {
         (0..count)
@@ -212,10 +701,8 @@ mod tests {
             .create();
 
         // Setup our Stacks client
-        let client_url = url::Url::parse(stacks_node_server.url().as_str()).unwrap();
-        let deployer =
-            StacksAddress::from_string("ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM").unwrap();
-        let client = StacksClient::new(client_url, deployer).unwrap();
+        let config = stacks_config(&[&stacks_node_server]);
+        let client = StacksClient::new(&config, &TlsConfig::default(), None).unwrap();
 
         // Make the request to the mock server
         let resp = client.get_current_signers_aggregate_key().await.unwrap();
@@ -224,4 +711,313 @@ mod tests {
         assert_eq!(resp, expected);
         mock.assert();
     }
+
+    #[test_case(&[], 1; "proxied")]
+    #[test_case(&["stacks.invalid"], 0; "no proxy")]
+    #[tokio::test]
+    async fn requests_go_through_proxy(no_proxy: &[&str], proxied: usize) {
+        let raw_json_response = format!(
+            r#"{{"data":"0x{}"}}"#,
+            Value::serialize_to_hex(&Value::buff_from_byte(0)).unwrap()
+        );
+
+        // The mock server stands in for the proxy, receiving the requests
+        // for the unresolvable Stacks node
+        let mut proxy_server = mockito::Server::new_async().await;
+        let mock = proxy_server
+            .mock("GET", "/v2/data_var/ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM/sbtc-registry/current-aggregate-pubkey?proof=0")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(&raw_json_response)
+            .expect(proxied)
+            .create_async()
+            .await;
+
+        let proxy = ProxyConfig {
+            url: proxy_server.url().parse().unwrap(),
+            no_proxy: no_proxy.iter().map(ToString::to_string).collect(),
+        };
+        let config = StacksConfig {
+            rpc_endpoint: vec!["http://stacks.invalid:20443".parse().unwrap()],
+            ..stacks_config(&[])
+        };
+        let client = StacksClient::new(&config, &TlsConfig::default(), Some(&proxy)).unwrap();
+
+        let resp = client.get_current_signers_aggregate_key().await;
+
+        assert_eq!(resp.is_ok(), proxied == 1);
+        mock.assert_async().await;
+    }
+
+    #[test]
+    fn new_requires_an_endpoint() {
+        let result = StacksClient::new(&stacks_config(&[]), &TlsConfig::default(), None);
+
+        assert!(matches!(
+            result,
+            Err(Error::Config(SpoxConfigError::NoStacksEndpoint))
+        ));
+    }
+
+    #[test_case(None; "no quorum")]
+    #[test_case(NonZero::new(1); "quorum of one")]
+    fn new_rejects_compare_proofs_without_quorum(quorum: Option<NonZero<usize>>) {
+        let server = mockito::Server::new();
+        let config = StacksConfig {
+            quorum,
+            compare_proofs: true,
+            ..stacks_config(&[&server])
+        };
+
+        let result = StacksClient::new(&config, &TlsConfig::default(), None);
+
+        assert!(matches!(
+            result,
+            Err(Error::Config(SpoxConfigError::StacksProofsWithoutQuorum))
+        ));
+    }
+
+    #[tokio::test]
+    async fn get_data_var_retries_and_fails_over() {
+        let aggregate_key = generate_pubkeys(1)[0];
+
+        let mut primary = mockito::Server::new_async().await;
+        let mut backup = mockito::Server::new_async().await;
+        let primary_mock = primary
+            .mock("GET", AGGREGATE_KEY_PATH)
+            .with_status(503)
+            .expect(2)
+            .create_async()
+            .await;
+        let backup_mock = backup
+            .mock("GET", AGGREGATE_KEY_PATH)
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(aggregate_key_response(&aggregate_key))
+            .expect(1)
+            .create_async()
+            .await;
+
+        let config = StacksConfig {
+            max_retries: 1,
+            ..stacks_config(&[&primary, &backup])
+        };
+        let client = StacksClient::new(&config, &TlsConfig::default(), None).unwrap();
+
+        let resp = client.get_current_signers_aggregate_key().await.unwrap();
+
+        assert_eq!(resp, Some(aggregate_key.into()));
+        primary_mock.assert_async().await;
+        backup_mock.assert_async().await;
+    }
+
+    #[test_case(true; "agreeing endpoints")]
+    #[test_case(false; "disagreeing endpoints")]
+    #[tokio::test]
+    async fn get_current_signers_aggregate_key_requires_quorum(agree: bool) {
+        let keys = generate_pubkeys(2);
+
+        let mut servers = Vec::new();
+        let mut mocks = Vec::new();
+        for key in [keys[0], if agree { keys[0] } else { keys[1] }] {
+            let mut server = mockito::Server::new_async().await;
+            let mock = server
+                .mock("GET", AGGREGATE_KEY_PATH)
+                .with_status(200)
+                .with_header("content-type", "application/json")
+                .with_body(aggregate_key_response(&key))
+                .expect(1)
+                .create_async()
+                .await;
+            servers.push(server);
+            mocks.push(mock);
+        }
+
+        let config = StacksConfig {
+            quorum: NonZero::new(2),
+            ..stacks_config(&servers.iter().collect::<Vec<_>>())
+        };
+        let client = StacksClient::new(&config, &TlsConfig::default(), None).unwrap();
+
+        let resp = client.get_current_signers_aggregate_key().await;
+
+        if agree {
+            assert_eq!(resp.unwrap(), Some(keys[0].into()));
+        } else {
+            assert!(matches!(resp, Err(Error::StacksQuorumNotReached(2, 1))));
+        }
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+    }
+
+    #[test_case(true; "agreeing proofs")]
+    #[test_case(false; "disagreeing proofs")]
+    #[tokio::test]
+    async fn get_current_signers_aggregate_key_compares_proofs(agree: bool) {
+        let aggregate_key = generate_pubkeys(1)[0];
+        let consensus_hash = ConsensusHash([1; 20]);
+        let block_hash = BlockHeaderHash([2; 32]);
+        let tip = StacksBlockId::new(&consensus_hash, &block_hash);
+        let path = AGGREGATE_KEY_PATH.replace("proof=0", &format!("proof=1&tip={}", tip.to_hex()));
+
+        let mut servers = Vec::new();
+        let mut mocks = Vec::new();
+        for proof in ["0x01", if agree { "0x01" } else { "0x02" }] {
+            let value = Value::Sequence(SequenceData::Buffer(BuffData {
+                data: aggregate_key.inner.serialize().to_vec(),
+            }));
+            let body = format!(
+                r#"{{"data":"0x{}","proof":"{proof}"}}"#,
+                Value::serialize_to_hex(&value).unwrap()
+            );
+            let mut server = mockito::Server::new_async().await;
+            let mock = server
+                .mock("GET", path.as_str())
+                .with_status(200)
+                .with_header("content-type", "application/json")
+                .with_body(body)
+                .expect(1)
+                .create_async()
+                .await;
+            servers.push(server);
+            mocks.push(mock);
+        }
+        // The proofs are read at the tip of the first endpoint
+        let info = serde_json::json!({
+            "stacks_tip": block_hash.to_hex(),
+            "stacks_tip_consensus_hash": consensus_hash.to_hex(),
+        });
+        let info_mock = servers[0]
+            .mock("GET", "/v2/info")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(info.to_string())
+            .expect(1)
+            .create_async()
+            .await;
+        mocks.push(info_mock);
+
+        let config = StacksConfig {
+            quorum: NonZero::new(2),
+            compare_proofs: true,
+            ..stacks_config(&servers.iter().collect::<Vec<_>>())
+        };
+        let client = StacksClient::new(&config, &TlsConfig::default(), None).unwrap();
+
+        let resp = client.get_current_signers_aggregate_key().await;
+
+        if agree {
+            assert_eq!(resp.unwrap(), Some(aggregate_key.into()));
+        } else {
+            assert!(matches!(resp, Err(Error::StacksQuorumNotReached(2, 1))));
+        }
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn get_current_signer_data_calls_read_only() {
+        let keys = generate_pubkeys(3);
+        let signer_set = Value::cons_list_unsanitized(
+            keys.iter()
+                .map(|key| Value::buff_from(key.inner.serialize().to_vec()).unwrap())
+                .collect(),
+        )
+        .unwrap();
+        let signer_principal = PrincipalData::parse(DEPLOYER).unwrap();
+        let data = TupleData::from_data(vec![
+            (
+                ClarityName::from("current-aggregate-pubkey"),
+                Value::buff_from(keys[0].inner.serialize().to_vec()).unwrap(),
+            ),
+            (ClarityName::from("current-signer-set"), signer_set),
+            (
+                ClarityName::from("current-signature-threshold"),
+                Value::UInt(2),
+            ),
+            (
+                ClarityName::from("current-signer-principal"),
+                Value::Principal(signer_principal.clone()),
+            ),
+        ])
+        .unwrap();
+        let body = format!(
+            r#"{{"okay":true,"result":"0x{}"}}"#,
+            Value::Tuple(data).serialize_to_hex().unwrap()
+        );
+
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "POST",
+                format!("/v2/contracts/call-read/{DEPLOYER}/sbtc-registry/get-current-signer-data")
+                    .as_str(),
+            )
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "sender": DEPLOYER,
+                "arguments": [],
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(body)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let config = stacks_config(&[&server]);
+        let client = StacksClient::new(&config, &TlsConfig::default(), None).unwrap();
+
+        let signer_data = client.get_current_signer_data().await.unwrap();
+
+        assert_eq!(signer_data.aggregate_key, Some(keys[0].into()));
+        assert_eq!(signer_data.signer_set, keys);
+        assert_eq!(signer_data.signature_threshold, 2);
+        assert_eq!(signer_data.signer_principal, signer_principal);
+        mock.assert_async().await;
+    }
+
+    #[test_case(Some(true); "completed")]
+    #[test_case(Some(false); "recorded as not completed")]
+    #[test_case(None; "not processed")]
+    #[tokio::test]
+    async fn get_deposit_status_reads_map_entry(status: Option<bool>) {
+        let outpoint = OutPoint::new(bitcoin::Txid::from_byte_array([1; 32]), 2);
+        let entry = Value::Optional(clarity::vm::types::OptionalData {
+            data: status.map(|status| Box::new(Value::Bool(status))),
+        });
+        let body = format!(r#"{{"data":"0x{}"}}"#, entry.serialize_to_hex().unwrap());
+        let key = TupleData::from_data(vec![
+            (
+                ClarityName::from("txid"),
+                Value::buff_from(vec![1; 32]).unwrap(),
+            ),
+            (ClarityName::from("vout-index"), Value::UInt(2)),
+        ])
+        .unwrap();
+
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "POST",
+                format!("/v2/map_entry/{DEPLOYER}/sbtc-registry/deposit-status?proof=0").as_str(),
+            )
+            .match_body(mockito::Matcher::Json(serde_json::json!(format!(
+                "0x{}",
+                Value::Tuple(key).serialize_to_hex().unwrap()
+            ))))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(body)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let config = stacks_config(&[&server]);
+        let client = StacksClient::new(&config, &TlsConfig::default(), None).unwrap();
+
+        assert_eq!(client.get_deposit_status(&outpoint).await.unwrap(), status);
+        mock.assert_async().await;
+    }
 }
@@ -1,8 +1,14 @@
 //! Module with testing utility functions.
 #![allow(clippy::unwrap_in_result, clippy::unwrap_used, clippy::expect_used)]
 
+use bitcoin::secp256k1::SECP256K1;
+use bitcoin::{NetworkKind, PrivateKey, PublicKey, ScriptBuf, XOnlyPublicKey};
+use clarity::vm::types::PrincipalData;
+use sbtc::deposits::{DepositScriptInputs, ReclaimScriptInputs};
+
 use crate::config::error::SpoxConfigError;
 use crate::config::{CONFIG_PREFIX, Settings};
+use crate::deposit_monitor::MonitoredDeposit;
 
 /// The path for the configuration file that we should use during testing.
 pub const DEFAULT_CONFIG_PATH: Option<&str> = Some("./src/config/default.toml");
@@ -40,3 +46,20 @@ pub fn set_var<K: AsRef<std::ffi::OsStr>, V: AsRef<std::ffi::OsStr>>(key: K, val
         std::env::set_var(key, value);
     }
 }
+
+/// A testnet monitored deposit to a random signers key, with an empty
+/// reclaim script
+pub fn monitored_deposit(alias: &str) -> MonitoredDeposit {
+    let public_key =
+        PublicKey::from_private_key(SECP256K1, &PrivateKey::generate(NetworkKind::Test));
+
+    MonitoredDeposit {
+        alias: alias.to_string(),
+        deposit_script_inputs: DepositScriptInputs {
+            signers_public_key: XOnlyPublicKey::from(public_key),
+            recipient: PrincipalData::parse("ST3497E9JFQ7KB9VEHAZRWYKF3296WQZEXBPXG193").unwrap(),
+            max_fee: 20_000,
+        },
+        reclaim_script_inputs: ReclaimScriptInputs::try_new(10, ScriptBuf::new()).unwrap(),
+    }
+}
@@ -0,0 +1,80 @@
+//! TLS setup for the outbound connections, trusting custom CA certificates
+//! and presenting a client certificate for mutual TLS if configured
+
+use std::path::Path;
+use std::sync::Arc;
+
+use rustls::pki_types::pem::PemObject as _;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ClientConfig, RootCertStore};
+
+use crate::config::TlsConfig;
+use crate::error::Error;
+
+/// Apply the TLS config to a reqwest client builder
+pub fn configure_reqwest(
+    mut builder: reqwest::ClientBuilder,
+    config: &TlsConfig,
+) -> Result<reqwest::ClientBuilder, Error> {
+    for path in &config.ca_certs {
+        let pem = read(path)?;
+        for cert in reqwest::Certificate::from_pem_bundle(&pem)
+            .map_err(|error| Error::TlsFile(path.to_path_buf(), error.to_string()))?
+        {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    if let (Some(cert_path), Some(key_path)) = (&config.client_cert, &config.client_key) {
+        let mut pem = read(cert_path)?;
+        pem.push(b'\n');
+        pem.extend(read(key_path)?);
+        let identity = reqwest::Identity::from_pem(&pem)
+            .map_err(|error| Error::TlsFile(cert_path.to_path_buf(), error.to_string()))?;
+        builder = builder.identity(identity);
+    }
+
+    Ok(builder)
+}
+
+/// Build a rustls client config from the TLS config, trusting the default
+/// roots and the configured CA certificates
+pub fn rustls_config(config: &TlsConfig) -> Result<Arc<ClientConfig>, Error> {
+    let mut roots = RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    for path in &config.ca_certs {
+        for cert in load_certs(path)? {
+            roots
+                .add(cert)
+                .map_err(|error| Error::TlsFile(path.to_path_buf(), error.to_string()))?;
+        }
+    }
+
+    let builder =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(|error| Error::Tls(error.to_string()))?
+            .with_root_certificates(roots);
+
+    let client_config = match (&config.client_cert, &config.client_key) {
+        (Some(cert_path), Some(key_path)) => {
+            let key = PrivateKeyDer::from_pem_file(key_path)
+                .map_err(|error| Error::TlsFile(key_path.to_path_buf(), error.to_string()))?;
+            builder
+                .with_client_auth_cert(load_certs(cert_path)?, key)
+                .map_err(|error| Error::Tls(error.to_string()))?
+        }
+        _ => builder.with_no_client_auth(),
+    };
+
+    Ok(Arc::new(client_config))
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, Error> {
+    CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|error| Error::TlsFile(path.to_path_buf(), error.to_string()))
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, Error> {
+    std::fs::read(path).map_err(|error| Error::TlsFile(path.to_path_buf(), error.to_string()))
+}